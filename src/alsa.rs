@@ -0,0 +1,322 @@
+//! A minimal client of the ALSA control interface, talking to the kernel
+//! through `/dev/snd/controlC*` directly instead of linking libasound.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read};
+use std::mem;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::OpenOptionsExt;
+use tokio::io::unix::AsyncFd;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+const ELEM_IFACE_MIXER: i32 = 2;
+const ELEM_TYPE_BOOLEAN: i32 = 1;
+const ELEM_TYPE_INTEGER: i32 = 2;
+const EVENT_ELEM: i32 = 0;
+const EVENT_MASK_VALUE: u32 = 1;
+const EVENT_MASK_REMOVE: u32 = !0;
+// struct snd_ctl_event: the type, the mask and the id of the element
+const EVENT_SIZE: usize = 72;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct ElemId {
+    numid: u32,
+    iface: i32,
+    device: u32,
+    subdevice: u32,
+    name: [u8; 44],
+    index: u32,
+}
+
+#[repr(C)]
+struct ElemList {
+    offset: u32,
+    space: u32,
+    used: u32,
+    count: u32,
+    pids: *mut ElemId,
+    reserved: [u8; 50],
+}
+
+#[repr(C)]
+struct ElemInfo {
+    id: ElemId,
+    kind: i32,
+    access: u32,
+    count: u32,
+    owner: i32,
+    // min, max and step of integer elements
+    value: [i64; 16],
+    reserved: [u8; 64],
+}
+
+#[repr(C)]
+struct ElemValue {
+    id: ElemId,
+    indirect: u32,
+    value: [i64; 128],
+    reserved: [u8; 128],
+}
+
+// The ioctl numbers encode the struct sizes, so the layouts have to match
+// the kernel's exactly.
+const _: () = assert!(mem::size_of::<ElemId>() == 64);
+const _: () = assert!(mem::size_of::<ElemList>() == 80);
+const _: () = assert!(mem::size_of::<ElemInfo>() == 272);
+const _: () = assert!(mem::size_of::<ElemValue>() == 1224);
+
+const fn iowr(nr: u64, size: usize) -> u64 {
+    (3 << 30) | ((size as u64) << 16) | ((b'U' as u64) << 8) | nr
+}
+
+const IOCTL_ELEM_LIST: u64 = iowr(0x10, mem::size_of::<ElemList>());
+const IOCTL_ELEM_INFO: u64 = iowr(0x11, mem::size_of::<ElemInfo>());
+const IOCTL_ELEM_READ: u64 = iowr(0x12, mem::size_of::<ElemValue>());
+const IOCTL_SUBSCRIBE_EVENTS: u64 = iowr(0x16, mem::size_of::<i32>());
+
+fn ioctl<T>(file: &File, request: u64, arg: &mut T) -> io::Result<()> {
+    // SAFETY: `arg` is one of the #[repr(C)] structs above whose size is
+    // encoded in `request`.
+    let result = unsafe { libc::ioctl(file.as_raw_fd(), request as _, arg as *mut T) };
+
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Whether a mixer control plays audio or records it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Playback,
+    Capture,
+}
+
+impl Direction {
+    fn name(self) -> &'static str {
+        match self {
+            Direction::Playback => "Playback",
+            Direction::Capture => "Capture",
+        }
+    }
+}
+
+/// Resolves a card given by index, e.g. `0`, or by id, e.g. `PCH`, to its
+/// index.
+fn card_index(card: &str) -> Result<u32, anyhow::Error> {
+    match card.parse::<u32>() {
+        Ok(x) => Ok(x),
+        Err(_) => fs::read_link(format!("/proc/asound/{card}"))
+            .ok()
+            .and_then(|x| x.to_str()?.strip_prefix("card")?.parse::<u32>().ok())
+            .ok_or_else(|| anyhow::anyhow!("Unknown sound card {card}")),
+    }
+}
+
+fn open(card: &str, nonblocking: bool) -> Result<File, anyhow::Error> {
+    let path = format!("/dev/snd/controlC{}", card_index(card)?);
+    let flags = if nonblocking { libc::O_NONBLOCK } else { 0 };
+
+    OpenOptions::new()
+        .read(true)
+        .custom_flags(flags | libc::O_CLOEXEC)
+        .open(&path)
+        .map_err(|x| anyhow::anyhow!("Could not open {path}: {x}"))
+}
+
+fn elem_name(id: &ElemId) -> &[u8] {
+    let end = id
+        .name
+        .iter()
+        .position(|x| *x == 0)
+        .unwrap_or(id.name.len());
+    &id.name[..end]
+}
+
+fn list_elems(file: &File) -> io::Result<Vec<ElemId>> {
+    // SAFETY: the struct consists of integers and a pointer, all of which
+    // are valid when zeroed.
+    let mut list: ElemList = unsafe { mem::zeroed() };
+    ioctl(file, IOCTL_ELEM_LIST, &mut list)?;
+
+    // SAFETY: see above
+    let mut ids = vec![unsafe { mem::zeroed::<ElemId>() }; list.count as usize];
+    list.space = list.count;
+    list.pids = ids.as_mut_ptr();
+    ioctl(file, IOCTL_ELEM_LIST, &mut list)?;
+    ids.truncate(list.used as usize);
+
+    Ok(ids)
+}
+
+fn elem_info(file: &File, id: &ElemId) -> io::Result<ElemInfo> {
+    // SAFETY: the struct consists of integers only.
+    let mut info: ElemInfo = unsafe { mem::zeroed() };
+    info.id.numid = id.numid;
+    ioctl(file, IOCTL_ELEM_INFO, &mut info)?;
+
+    Ok(info)
+}
+
+fn elem_values(file: &File, info: &ElemInfo) -> io::Result<Vec<i64>> {
+    // SAFETY: the struct consists of integers only.
+    let mut value: ElemValue = unsafe { mem::zeroed() };
+    value.id.numid = info.id.numid;
+    ioctl(file, IOCTL_ELEM_READ, &mut value)?;
+
+    let count = (info.count as usize).min(value.value.len());
+    Ok(value.value[..count].to_vec())
+}
+
+/// The volume and mute switch of a mixer control, e.g. `Master`.
+pub struct Mixer {
+    file: File,
+    volume: Option<ElemInfo>,
+    switch: Option<ElemInfo>,
+}
+
+impl Mixer {
+    /// Opens the volume and switch of `control` in `direction`, e.g. the
+    /// `Master Playback Volume` element for `Master`.
+    pub fn open(card: &str, control: &str, direction: Direction) -> Result<Self, anyhow::Error> {
+        let file = open(card, false)?;
+        let volume_name = format!("{control} {} Volume", direction.name());
+        let switch_name = format!("{control} {} Switch", direction.name());
+
+        let mut volume = None;
+        let mut switch = None;
+
+        for id in list_elems(&file)? {
+            if id.iface != ELEM_IFACE_MIXER || id.index != 0 {
+                continue;
+            }
+
+            let name = elem_name(&id);
+            if name == volume_name.as_bytes() {
+                let info = elem_info(&file, &id)?;
+                if info.kind == ELEM_TYPE_INTEGER {
+                    volume = Some(info);
+                }
+            } else if name == switch_name.as_bytes() {
+                let info = elem_info(&file, &id)?;
+                if info.kind == ELEM_TYPE_BOOLEAN {
+                    switch = Some(info);
+                }
+            }
+        }
+
+        if volume.is_none() && switch.is_none() {
+            return Err(anyhow::anyhow!("No control {control} on sound card {card}"));
+        }
+
+        Ok(Self {
+            file,
+            volume,
+            switch,
+        })
+    }
+
+    /// Returns the volume in percent of the range of the control, averaged
+    /// over its channels, as amixer does.
+    pub fn volume(&self) -> Result<u32, anyhow::Error> {
+        let Some(info) = &self.volume else {
+            return Ok(100);
+        };
+
+        let (min, max) = (info.value[0], info.value[1]);
+        let values = elem_values(&self.file, info)?;
+        if values.is_empty() || max <= min {
+            return Ok(0);
+        }
+
+        let average = values.iter().sum::<i64>() as f64 / values.len() as f64;
+        Ok(((average - min as f64) * 100.0 / (max - min) as f64).round() as u32)
+    }
+
+    /// A control is muted if the switch of any of its channels is off.
+    pub fn muted(&self) -> Result<bool, anyhow::Error> {
+        let Some(info) = &self.switch else {
+            return Ok(false);
+        };
+
+        Ok(elem_values(&self.file, info)?.contains(&0))
+    }
+}
+
+/// Whether any capture device of `card` is currently recording, according
+/// to the state of its substreams in `/proc/asound`.
+pub fn capturing(card: &str) -> Result<bool, anyhow::Error> {
+    let path = format!("/proc/asound/card{}", card_index(card)?);
+
+    for device in fs::read_dir(&path)?.flatten() {
+        let name = device.file_name();
+        let name = name.to_string_lossy();
+        if !(name.starts_with("pcm") && name.ends_with('c')) {
+            continue;
+        }
+
+        for substream in fs::read_dir(device.path())?.flatten() {
+            if !substream.file_name().to_string_lossy().starts_with("sub") {
+                continue;
+            }
+
+            let status = fs::read_to_string(substream.path().join("status")).unwrap_or_default();
+            if status.lines().any(|x| x.trim() == "state: RUNNING") {
+                return Ok(true);
+            }
+        }
+    }
+
+    Ok(false)
+}
+
+/// Returns a channel that yields whenever the value of a control of `card`
+/// changes, and the task that reads the events.
+pub fn subscribe(
+    card: &str,
+) -> Result<(mpsc::UnboundedReceiver<()>, JoinHandle<()>), anyhow::Error> {
+    let file = open(card, true)?;
+    let mut enable: i32 = 1;
+    ioctl(&file, IOCTL_SUBSCRIBE_EVENTS, &mut enable)?;
+
+    let mut file = AsyncFd::new(file)?;
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    let task = tokio::spawn(async move {
+        let mut buffer = [0; EVENT_SIZE * 16];
+
+        loop {
+            let mut guard = match file.readable_mut().await {
+                Ok(x) => x,
+                Err(x) => {
+                    eprintln!("Error while waiting for mixer events: {x}");
+                    break;
+                }
+            };
+
+            let length = match guard.try_io(|x| x.get_mut().read(&mut buffer)) {
+                Ok(Ok(x)) => x,
+                Ok(Err(x)) => {
+                    eprintln!("Error while reading mixer events: {x}");
+                    break;
+                }
+                Err(_would_block) => continue,
+            };
+
+            let changed = buffer[..length].chunks_exact(EVENT_SIZE).any(|x| {
+                let kind = i32::from_ne_bytes([x[0], x[1], x[2], x[3]]);
+                let mask = u32::from_ne_bytes([x[4], x[5], x[6], x[7]]);
+                kind == EVENT_ELEM && mask != EVENT_MASK_REMOVE && mask & EVENT_MASK_VALUE != 0
+            });
+
+            if changed && tx.send(()).is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok((rx, task))
+}
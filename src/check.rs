@@ -0,0 +1,237 @@
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::config::RsbrConfig;
+use crate::format::SnippetError;
+use crate::property::{self, ModuleContext, ShowBars};
+
+/// Validates the config file at `config_path` without starting the bar.
+/// Every error is printed as `path:line:column: error: message`. Returns
+/// whether the config is valid.
+pub fn check(config_path: &Path) -> bool {
+    let path = config_path.display();
+
+    let source = match fs::read_to_string(config_path) {
+        Ok(x) => x,
+        Err(x) => {
+            eprintln!("{path}: error: {x}");
+            return false;
+        }
+    };
+
+    let mut config = match RsbrConfig::from_str(&source) {
+        Ok(x) => x,
+        Err(x) => {
+            let (line, column) = x
+                .span()
+                .map(|span| position(&source, span.start))
+                .unwrap_or((1, 1));
+            eprintln!("{path}:{line}:{column}: error: {}", x.message());
+            return false;
+        }
+    };
+
+    if let Err(x) = config.theme.load_source() {
+        eprintln!("{path}: error: {x}");
+        return false;
+    }
+
+    // Modules that are not placed in `format` are checked as well.
+    let attributes = ShowBars::new(property::from_config(&config));
+    let errors = attributes.errors(&config);
+
+    for error in &errors {
+        // Semantic errors know the text that caused them, so its first
+        // occurrence in the tables of the module, or at the top level for
+        // other errors, is reported as their location. Without the text, a
+        // module error points at the table of the module.
+        let (ranges, fallback) = match error.downcast_ref::<ModuleContext>() {
+            Some(x) => {
+                let ranges = module_ranges(&source, x.kind, &x.name);
+                let start = ranges.first().map(|x| x.start);
+                (ranges, start)
+            }
+            None => (vec![top_level_range(&source)], None),
+        };
+        let offset = error
+            .chain()
+            .find_map(|x| x.downcast_ref::<SnippetError>())
+            .and_then(|x| {
+                ranges
+                    .iter()
+                    .find_map(|range| Some(range.start + source[range.clone()].find(&x.snippet)?))
+            })
+            .or(fallback);
+
+        match offset.map(|x| position(&source, x)) {
+            Some((line, column)) => eprintln!("{path}:{line}:{column}: error: {error:#}"),
+            None => eprintln!("{path}: error: {error:#}"),
+        }
+    }
+
+    if errors.is_empty() {
+        println!("{path}: ok");
+    }
+
+    errors.is_empty()
+}
+
+/// Returns the path of every table header, e.g. `custom.states.low` of
+/// `[custom.states.low]`, and the range from the header to the next one.
+fn headers(source: &str) -> Vec<(String, Range<usize>)> {
+    let mut headers: Vec<(String, Range<usize>)> = vec![];
+    let mut offset = 0;
+
+    for line in source.split_inclusive('\n') {
+        let header = line
+            .trim()
+            .strip_prefix('[')
+            .and_then(|x| x.split_once(']'))
+            .map(|(x, _)| x.trim_start_matches('[').trim());
+
+        if let Some(path) = header {
+            if let Some(last) = headers.last_mut() {
+                last.1.end = offset;
+            }
+            headers.push((path.replace(' ', ""), offset..source.len()));
+        }
+        offset += line.len();
+    }
+
+    headers
+}
+
+/// The range of the keys in front of the first table header.
+fn top_level_range(source: &str) -> Range<usize> {
+    0..headers(source).first().map_or(source.len(), |x| x.1.start)
+}
+
+/// Returns the ranges of the tables of the module instance `name` of type
+/// `kind`: its `[kind]` or `[[kind]]` table and the following sub-tables,
+/// e.g. `[kind.states]`.
+fn module_ranges(source: &str, kind: &str, name: &str) -> Vec<Range<usize>> {
+    let prefix = format!("{kind}.");
+    let mut instances: Vec<Vec<Range<usize>>> = vec![];
+
+    for (path, range) in headers(source) {
+        if path == kind {
+            instances.push(vec![range]);
+        } else if path.starts_with(&prefix) {
+            // A sub-table in front of any table of the module, e.g.
+            // `[audio.states.muted]` without `[audio]`, defines it implicitly.
+            match instances.last_mut() {
+                Some(x) => x.push(range),
+                None => instances.push(vec![range]),
+            }
+        }
+    }
+
+    // An instance without a name key is named after its type.
+    let instance_name = |ranges: &Vec<Range<usize>>| {
+        source[ranges[0].clone()]
+            .lines()
+            .find_map(|x| {
+                let (key, value) = x.split_once('=')?;
+                (key.trim() == "name").then(|| value.trim().trim_matches(['"', '\'']))
+            })
+            .unwrap_or(kind)
+            .to_string()
+    };
+
+    // The `[kind]` table may also follow a sub-table that defined the module
+    // implicitly, so the instances of the same name are merged.
+    let mut ranges: Vec<Range<usize>> = instances
+        .into_iter()
+        .filter(|x| instance_name(x) == name)
+        .flatten()
+        .collect();
+    ranges.sort_by_key(|x| x.start);
+
+    ranges
+}
+
+/// Converts a byte offset into a 1-based line and column.
+fn position(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rfind('\n')
+        .map_or(before, |x| &before[x + 1..])
+        .chars()
+        .count()
+        + 1;
+
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_offsets_to_positions() {
+        let source = "format = \"{audio}\"\n\n[audio]\nfgcolor = \"teal\"\n";
+
+        assert_eq!(position(source, 0), (1, 1));
+        assert_eq!(position(source, 10), (1, 11));
+        assert_eq!(position(source, 19), (2, 1));
+        assert_eq!(position(source, source.find("teal").unwrap()), (4, 12));
+    }
+
+    #[test]
+    fn counts_columns_in_characters() {
+        let source = "icon = \"󰕾\" # x";
+
+        assert_eq!(position(source, source.find('#').unwrap()), (1, 12));
+    }
+
+    #[test]
+    fn finds_the_tables_of_a_module() {
+        let source = "format = \"{a}\"\n\
+                      [[custom]]\nname = \"a\"\n\
+                      [theme]\nred = \"#FF0000\"\n\
+                      [[custom]]\nname = 'b'\n\
+                      [custom.states.low]\nfgcolor = \"red\"\n\
+                      [custom]\n";
+        let tables = |kind, name| {
+            module_ranges(source, kind, name)
+                .into_iter()
+                .map(|x| source[x].lines().next().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(tables("custom", "a"), ["[[custom]]"]);
+        assert_eq!(tables("custom", "b"), ["[[custom]]", "[custom.states.low]"]);
+        assert_eq!(tables("custom", "custom"), ["[custom]"]);
+        assert!(tables("audio", "audio").is_empty());
+        assert_eq!(&source[top_level_range(source)], "format = \"{a}\"\n");
+    }
+
+    #[test]
+    fn finds_sub_tables_without_a_module_table() {
+        let source = "format = \"{audio}\"\n\
+                      [audio.states.mutd]\nfgcolor = \"red\"\n\
+                      [audio.icons]\nvolme = \"V\"\n";
+        let tables = module_ranges(source, "audio", "audio")
+            .into_iter()
+            .map(|x| source[x].lines().next().unwrap().to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(tables, ["[audio.states.mutd]", "[audio.icons]"]);
+
+        let source = "[audio.states.mutd]\n[theme]\n[audio]\n";
+        let tables = module_ranges(source, "audio", "audio")
+            .into_iter()
+            .map(|x| source[x].lines().next().unwrap().to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(tables, ["[audio.states.mutd]", "[audio]"]);
+    }
+
+    #[test]
+    fn clamps_offsets_past_the_end() {
+        assert_eq!(position("a\nb", 100), (2, 2));
+    }
+}
@@ -1,54 +1,185 @@
+use crate::format::FormatString;
 use crate::hexcolor::{Theme, ThemeColor};
-use serde::Deserialize;
+use crate::icons::{IconSet, Icons};
+use crate::markup::Markup;
+use crate::output::OutputKind;
+use crate::property::audio::AudioBackend;
+use crate::property::network::InterfaceSelection;
+use crate::state::States;
+use serde::de::{self, value::MapAccessDeserializer, value::SeqAccessDeserializer};
+use serde::{Deserialize, Deserializer};
+use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::marker::PhantomData;
+use std::path::Path;
 use std::str::FromStr;
-use toml;
 
 #[derive(Deserialize, Clone)]
-#[serde(default)]
+#[serde(default, deny_unknown_fields)]
 pub struct RsbrConfig {
     pub theme: Theme,
-    pub format: String,
-    pub datetime: RsbrDatetimeConfig,
-    pub audio: RsbrAudioConfig,
-    pub battery: RsbrBatteryConfig,
-    pub brightness: RsbrBrightnessConfig,
-    pub network: RsbrNetworkConfig,
+    pub format: FormatString,
+    pub output: OutputKind,
+    pub markup: Markup,
+    pub icons: IconSet,
+    #[serde(deserialize_with = "one_or_many")]
+    pub datetime: Vec<RsbrDatetimeConfig>,
+    #[serde(deserialize_with = "one_or_many")]
+    pub audio: Vec<RsbrAudioConfig>,
+    #[serde(deserialize_with = "one_or_many")]
+    pub microphone: Vec<RsbrMicrophoneConfig>,
+    #[serde(deserialize_with = "one_or_many")]
+    pub battery: Vec<RsbrBatteryConfig>,
+    #[serde(deserialize_with = "one_or_many")]
+    pub brightness: Vec<RsbrBrightnessConfig>,
+    #[serde(deserialize_with = "one_or_many")]
+    pub network: Vec<RsbrNetworkConfig>,
+    #[serde(deserialize_with = "one_or_many")]
+    pub bandwidth: Vec<RsbrBandwidthConfig>,
+    #[serde(deserialize_with = "one_or_many")]
+    pub vpn: Vec<RsbrVpnConfig>,
+    #[serde(deserialize_with = "one_or_many")]
+    pub custom: Vec<RsbrCustomConfig>,
 }
 
 #[derive(Deserialize, Clone)]
-#[serde(default)]
+#[serde(default, deny_unknown_fields)]
 pub struct RsbrDatetimeConfig {
+    pub name: Option<String>,
     pub format: String,
+    pub utc: bool,
+    pub interval: u64,
+    pub signal: Option<i32>,
+    pub timeout: u64,
     pub fgcolor: ThemeColor,
     pub bgcolor: ThemeColor,
 }
 
 #[derive(Deserialize, Clone)]
-#[serde(default)]
+#[serde(default, deny_unknown_fields)]
 pub struct RsbrBrightnessConfig {
+    pub name: Option<String>,
+    pub format: FormatString,
+    pub icons: Icons,
+    pub interval: u64,
+    pub signal: Option<i32>,
+    pub timeout: u64,
     pub fgcolor: ThemeColor,
     pub bgcolor: ThemeColor,
 }
 
 #[derive(Deserialize, Clone)]
-#[serde(default)]
+#[serde(default, deny_unknown_fields)]
 pub struct RsbrBatteryConfig {
+    pub name: Option<String>,
+    pub format: FormatString,
+    pub icons: Icons,
+    pub interval: u64,
+    pub signal: Option<i32>,
+    pub timeout: u64,
     pub fgcolor: ThemeColor,
     pub bgcolor: ThemeColor,
+    pub states: States,
 }
 
 #[derive(Deserialize, Clone)]
-#[serde(default)]
+#[serde(default, deny_unknown_fields)]
 pub struct RsbrAudioConfig {
+    pub name: Option<String>,
+    pub format: FormatString,
+    pub icons: Icons,
+    pub backend: AudioBackend,
+    #[serde(deserialize_with = "string_or_number")]
+    pub card: String,
+    pub control: String,
+    pub sink: Option<String>,
+    pub interval: u64,
+    pub signal: Option<i32>,
+    pub timeout: u64,
     pub fgcolor: ThemeColor,
     pub bgcolor: ThemeColor,
+    pub states: States,
 }
 
 #[derive(Deserialize, Clone)]
-#[serde(default)]
+#[serde(default, deny_unknown_fields)]
+pub struct RsbrMicrophoneConfig {
+    pub name: Option<String>,
+    pub format: FormatString,
+    pub icons: Icons,
+    pub backend: AudioBackend,
+    #[serde(deserialize_with = "string_or_number")]
+    pub card: String,
+    pub control: String,
+    pub source: Option<String>,
+    pub hide_idle: bool,
+    pub interval: u64,
+    pub signal: Option<i32>,
+    pub timeout: u64,
+    pub fgcolor: ThemeColor,
+    pub bgcolor: ThemeColor,
+    pub states: States,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
 pub struct RsbrNetworkConfig {
+    pub name: Option<String>,
+    pub format: FormatString,
+    pub icons: Icons,
+    pub interface: InterfaceSelection,
+    pub separator: String,
+    pub interval: u64,
+    pub signal: Option<i32>,
+    pub timeout: u64,
+    pub fgcolor: ThemeColor,
+    pub bgcolor: ThemeColor,
+    pub states: States,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct RsbrBandwidthConfig {
+    pub name: Option<String>,
+    pub format: FormatString,
+    pub icons: Icons,
+    pub interface: InterfaceSelection,
+    pub separator: String,
+    pub smoothing: f64,
+    pub interval: u64,
+    pub signal: Option<i32>,
+    pub timeout: u64,
+    pub fgcolor: ThemeColor,
+    pub bgcolor: ThemeColor,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct RsbrVpnConfig {
+    pub name: Option<String>,
+    pub format: FormatString,
+    pub format_disconnected: FormatString,
+    pub icons: Icons,
+    pub interface: InterfaceSelection,
+    pub separator: String,
+    pub interval: u64,
+    pub signal: Option<i32>,
+    pub timeout: u64,
+    pub fgcolor: ThemeColor,
+    pub bgcolor: ThemeColor,
+    pub states: States,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct RsbrCustomConfig {
+    pub name: Option<String>,
+    pub command: String,
+    pub on_click: Option<String>,
+    pub format: FormatString,
+    pub interval: u64,
+    pub signal: Option<i32>,
+    pub timeout: u64,
     pub fgcolor: ThemeColor,
     pub bgcolor: ThemeColor,
 }
@@ -56,7 +187,12 @@ pub struct RsbrNetworkConfig {
 impl Default for RsbrDatetimeConfig {
     fn default() -> Self {
         Self {
+            name: None,
             format: "%Y-%m-%d %H:%M:%S".to_string(),
+            utc: false,
+            interval: 1,
+            signal: None,
+            timeout: 1,
             fgcolor: ThemeColor::from_str("white").unwrap(),
             bgcolor: ThemeColor::from_str("black").unwrap(),
         }
@@ -66,6 +202,12 @@ impl Default for RsbrDatetimeConfig {
 impl Default for RsbrBrightnessConfig {
     fn default() -> Self {
         Self {
+            name: None,
+            format: FormatString::from_str("{icon} {percent}% ").unwrap(),
+            icons: Icons::default(),
+            interval: 1,
+            signal: None,
+            timeout: 1,
             fgcolor: ThemeColor::from_str("white").unwrap(),
             bgcolor: ThemeColor::from_str("black").unwrap(),
         }
@@ -75,8 +217,19 @@ impl Default for RsbrBrightnessConfig {
 impl Default for RsbrBatteryConfig {
     fn default() -> Self {
         Self {
+            name: None,
+            format: FormatString::from_str("{icon} {percent}% ").unwrap(),
+            icons: Icons::default(),
+            interval: 30,
+            signal: None,
+            timeout: 5,
             fgcolor: ThemeColor::from_str("white").unwrap(),
             bgcolor: ThemeColor::from_str("black").unwrap(),
+            states: States::with_fgcolors(&[
+                ("critical", "red"),
+                ("warning", "yellow"),
+                ("charging", "green"),
+            ]),
         }
     }
 }
@@ -84,8 +237,40 @@ impl Default for RsbrBatteryConfig {
 impl Default for RsbrAudioConfig {
     fn default() -> Self {
         Self {
+            name: None,
+            format: FormatString::from_str("{icon} {volume}%").unwrap(),
+            icons: Icons::default(),
+            backend: AudioBackend::Alsa,
+            card: "0".to_string(),
+            control: "Master".to_string(),
+            sink: None,
+            interval: 1,
+            signal: None,
+            timeout: 2,
             fgcolor: ThemeColor::from_str("white").unwrap(),
             bgcolor: ThemeColor::from_str("black").unwrap(),
+            states: States::with_fgcolors(&[("muted", "bright_black")]),
+        }
+    }
+}
+
+impl Default for RsbrMicrophoneConfig {
+    fn default() -> Self {
+        Self {
+            name: None,
+            format: FormatString::from_str("{icon} {volume}%").unwrap(),
+            icons: Icons::default(),
+            backend: AudioBackend::Alsa,
+            card: "0".to_string(),
+            control: "Capture".to_string(),
+            source: None,
+            hide_idle: false,
+            interval: 1,
+            signal: None,
+            timeout: 2,
+            fgcolor: ThemeColor::from_str("white").unwrap(),
+            bgcolor: ThemeColor::from_str("black").unwrap(),
+            states: States::with_fgcolors(&[("muted", "bright_black"), ("live", "red")]),
         }
     }
 }
@@ -93,6 +278,68 @@ impl Default for RsbrAudioConfig {
 impl Default for RsbrNetworkConfig {
     fn default() -> Self {
         Self {
+            name: None,
+            format: FormatString::from_str("{icon} {ssid}").unwrap(),
+            icons: Icons::default(),
+            interface: InterfaceSelection::Auto,
+            separator: " ".to_string(),
+            interval: 5,
+            signal: None,
+            timeout: 5,
+            fgcolor: ThemeColor::from_str("white").unwrap(),
+            bgcolor: ThemeColor::from_str("black").unwrap(),
+            states: States::with_fgcolors(&[("disconnected", "bright_black")]),
+        }
+    }
+}
+
+impl Default for RsbrBandwidthConfig {
+    fn default() -> Self {
+        Self {
+            name: None,
+            format: FormatString::from_str("{icon} ↓{rx} ↑{tx}").unwrap(),
+            icons: Icons::default(),
+            interface: InterfaceSelection::Auto,
+            separator: " ".to_string(),
+            smoothing: 0.0,
+            interval: 1,
+            signal: None,
+            timeout: 1,
+            fgcolor: ThemeColor::from_str("white").unwrap(),
+            bgcolor: ThemeColor::from_str("black").unwrap(),
+        }
+    }
+}
+
+impl Default for RsbrVpnConfig {
+    fn default() -> Self {
+        Self {
+            name: None,
+            format: FormatString::from_str("{icon} {name}").unwrap(),
+            format_disconnected: FormatString::from_str("{icon} No VPN").unwrap(),
+            icons: Icons::default(),
+            interface: InterfaceSelection::Auto,
+            separator: " ".to_string(),
+            interval: 5,
+            signal: None,
+            timeout: 5,
+            fgcolor: ThemeColor::from_str("white").unwrap(),
+            bgcolor: ThemeColor::from_str("black").unwrap(),
+            states: States::with_fgcolors(&[("disconnected", "red"), ("stale", "yellow")]),
+        }
+    }
+}
+
+impl Default for RsbrCustomConfig {
+    fn default() -> Self {
+        Self {
+            name: None,
+            command: String::new(),
+            on_click: None,
+            format: FormatString::from_str("{output}").unwrap(),
+            interval: 5,
+            signal: None,
+            timeout: 5,
             fgcolor: ThemeColor::from_str("white").unwrap(),
             bgcolor: ThemeColor::from_str("black").unwrap(),
         }
@@ -102,17 +349,69 @@ impl Default for RsbrNetworkConfig {
 impl Default for RsbrConfig {
     fn default() -> Self {
         RsbrConfig {
-            format: "{brightness} {battery} {datetime}".to_string(),
-            datetime: RsbrDatetimeConfig::default(),
-            battery: RsbrBatteryConfig::default(),
-            brightness: RsbrBrightnessConfig::default(),
-            audio: RsbrAudioConfig::default(),
-            network: RsbrNetworkConfig::default(),
+            format: FormatString::from_str("{brightness} {battery} {datetime}").unwrap(),
+            output: OutputKind::X11,
+            markup: Markup::Status2d,
+            icons: IconSet::Nerdfont,
+            datetime: vec![RsbrDatetimeConfig::default()],
+            battery: vec![RsbrBatteryConfig::default()],
+            brightness: vec![RsbrBrightnessConfig::default()],
+            audio: vec![RsbrAudioConfig::default()],
+            microphone: vec![RsbrMicrophoneConfig::default()],
+            network: vec![RsbrNetworkConfig::default()],
+            bandwidth: vec![RsbrBandwidthConfig::default()],
+            vpn: vec![RsbrVpnConfig::default()],
+            custom: vec![],
             theme: Theme::default(),
         }
     }
 }
 
+/// Accepts a module section either as a single table or as an array of tables.
+fn one_or_many<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    struct OneOrMany<T>(PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>> de::Visitor<'de> for OneOrMany<T> {
+        type Value = Vec<T>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a table or an array of tables")
+        }
+
+        fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+            Ok(vec![T::deserialize(MapAccessDeserializer::new(map))?])
+        }
+
+        fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+            Vec::deserialize(SeqAccessDeserializer::new(seq))
+        }
+    }
+
+    deserializer.deserialize_any(OneOrMany(PhantomData))
+}
+
+/// Accepts e.g. a sound card both by index and by name.
+fn string_or_number<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrNumber {
+        String(String),
+        Number(u64),
+    }
+
+    Ok(match StringOrNumber::deserialize(deserializer)? {
+        StringOrNumber::String(x) => x,
+        StringOrNumber::Number(x) => x.to_string(),
+    })
+}
+
 impl FromStr for RsbrConfig {
     type Err = toml::de::Error;
 
@@ -122,9 +421,10 @@ impl FromStr for RsbrConfig {
     }
 }
 
-pub fn read_config(config_path: &PathBuf) -> Result<RsbrConfig, anyhow::Error> {
+pub fn read_config(config_path: &Path) -> Result<RsbrConfig, anyhow::Error> {
     let config_content = fs::read_to_string(config_path)?;
-    let config = RsbrConfig::from_str(config_content.as_str())?;
+    let mut config = RsbrConfig::from_str(config_content.as_str())?;
+    config.theme.load_source()?;
 
     Ok(config)
 }
@@ -0,0 +1,243 @@
+use serde::de;
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// An error caused by a snippet of the config file, e.g. an unknown
+/// placeholder, so that `rsbr check` can point at its location.
+#[derive(Debug)]
+pub struct SnippetError {
+    pub snippet: String,
+    pub message: String,
+}
+
+impl fmt::Display for SnippetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for SnippetError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Literal(String),
+    Placeholder(String),
+}
+
+/// A format string parsed into literals and `{name}` placeholders.
+/// Literal braces are written as `{{` and `}}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatString(Vec<Token>);
+
+impl FormatString {
+    pub fn placeholders(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().filter_map(|token| match token {
+            Token::Placeholder(x) => Some(x.as_str()),
+            Token::Literal(_) => None,
+        })
+    }
+
+    /// Fails on the first placeholder that is not one of `names`.
+    pub fn validate(&self, names: &[&str]) -> Result<(), anyhow::Error> {
+        match self.placeholders().find(|x| !names.contains(x)) {
+            Some(x) => Err(SnippetError {
+                snippet: format!("{{{x}}}"),
+                message: format!("Unknown placeholder {{{x}}}"),
+            }
+            .into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Renders the format string in a single pass. Placeholders without a
+    /// value are left empty.
+    pub fn render<F, S>(&self, lookup: F) -> String
+    where
+        F: Fn(&str) -> Option<S>,
+        S: AsRef<str>,
+    {
+        self.render_escaped(lookup, |x| x.to_string())
+    }
+
+    /// Like `render`, but passes the literal text through `escape`, e.g.
+    /// for a markup language.
+    pub fn render_escaped<F, S, E>(&self, lookup: F, escape: E) -> String
+    where
+        F: Fn(&str) -> Option<S>,
+        S: AsRef<str>,
+        E: Fn(&str) -> String,
+    {
+        let mut output = String::new();
+
+        for token in &self.0 {
+            match token {
+                Token::Literal(x) => output.push_str(&escape(x)),
+                Token::Placeholder(x) => {
+                    if let Some(x) = lookup(x) {
+                        output.push_str(x.as_ref());
+                    }
+                }
+            }
+        }
+
+        output
+    }
+
+    pub fn render_fields(&self, fields: &[(&str, String)]) -> String {
+        self.render(|name| {
+            fields
+                .iter()
+                .find(|(x, _)| *x == name)
+                .map(|(_, x)| x.as_str())
+        })
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
+
+/// Whether `name` can be used as a placeholder, e.g. the name of a module.
+pub fn is_placeholder(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(is_name_char)
+}
+
+impl FromStr for FormatString {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = vec![];
+        let mut literal = String::new();
+        let mut chars = s.char_indices().peekable();
+
+        while let Some((pos, c)) = chars.next() {
+            match c {
+                '{' if chars.next_if(|&(_, x)| x == '{').is_some() => literal.push('{'),
+                '}' if chars.next_if(|&(_, x)| x == '}').is_some() => literal.push('}'),
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some((_, '}')) => break,
+                            Some((_, x)) if is_name_char(x) => name.push(x),
+                            Some((x, _)) => {
+                                return Err(anyhow::anyhow!(
+                                    "Invalid character in placeholder at position {x} of \"{s}\""
+                                ))
+                            }
+                            None => {
+                                return Err(anyhow::anyhow!(
+                                    "Unclosed placeholder at position {pos} of \"{s}\""
+                                ))
+                            }
+                        }
+                    }
+
+                    if name.is_empty() {
+                        return Err(anyhow::anyhow!(
+                            "Empty placeholder at position {pos} of \"{s}\""
+                        ));
+                    }
+
+                    if !literal.is_empty() {
+                        tokens.push(Token::Literal(std::mem::take(&mut literal)));
+                    }
+                    tokens.push(Token::Placeholder(name));
+                }
+                '}' => {
+                    return Err(anyhow::anyhow!(
+                        "Unmatched }} at position {pos} of \"{s}\", use }}}} for a literal brace"
+                    ))
+                }
+                x => literal.push(x),
+            }
+        }
+
+        if !literal.is_empty() {
+            tokens.push(Token::Literal(literal));
+        }
+
+        Ok(FormatString(tokens))
+    }
+}
+
+impl<'de> Deserialize<'de> for FormatString {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s: String = Deserialize::deserialize(deserializer)?;
+
+        match FormatString::from_str(&s) {
+            Ok(x) => Ok(x),
+            Err(x) => Err(de::Error::custom(x)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(format: &str, fields: &[(&str, String)]) -> String {
+        FormatString::from_str(format)
+            .unwrap()
+            .render_fields(fields)
+    }
+
+    #[test]
+    fn parses_literals_and_placeholders() {
+        let format = FormatString::from_str("{icon} {volume}%").unwrap();
+
+        assert_eq!(
+            format.0,
+            vec![
+                Token::Placeholder("icon".to_string()),
+                Token::Literal(" ".to_string()),
+                Token::Placeholder("volume".to_string()),
+                Token::Literal("%".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn escapes_braces() {
+        assert_eq!(
+            render("{{{output}}} }}{{", &[("output", "x".to_string())]),
+            "{x} }{"
+        );
+    }
+
+    #[test]
+    fn leaves_missing_fields_empty() {
+        assert_eq!(render("[{a}|{b}]", &[("a", "1".to_string())]), "[1|]");
+    }
+
+    #[test]
+    fn rejects_unknown_placeholders() {
+        let format = FormatString::from_str("{icon} {volme}").unwrap();
+        let error = format.validate(&["icon", "volume"]).unwrap_err();
+        let error = error.downcast_ref::<SnippetError>().unwrap();
+
+        assert_eq!(error.snippet, "{volme}");
+        assert!(format.validate(&["icon", "volme"]).is_ok());
+    }
+
+    #[test]
+    fn checks_placeholder_names() {
+        for name in ["icon", "local-time", "cpu_0", "Wifi2"] {
+            assert!(is_placeholder(name), "{name}");
+        }
+        for name in ["", "my clock", "{icon}", "uhr:zeit", "zeit\u{e4}"] {
+            assert!(!is_placeholder(name), "{name}");
+        }
+    }
+
+    #[test]
+    fn rejects_unterminated_braces() {
+        for format in ["{icon", "icon}", "{icon}}", "{}", "{ico n}", "{{icon}"] {
+            assert!(FormatString::from_str(format).is_err(), "{format}");
+        }
+    }
+}
@@ -1,13 +1,65 @@
-use anyhow;
 use regex::Regex;
 use serde::de;
 use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
 use std::fmt;
+use std::path::PathBuf;
 use std::str::FromStr;
 
-#[derive(Debug, Clone)]
+use crate::format::SnippetError;
+use crate::themesource::ThemeSource;
+
+/// A `#rrggbb` or `#rrggbbaa` color.
+#[derive(Debug, Clone, PartialEq)]
 pub struct HexColor(pub String);
 
+impl HexColor {
+    pub fn rgb(&self) -> (u8, u8, u8) {
+        let (r, g, b, _) = self.rgba();
+        (r, g, b)
+    }
+
+    pub fn rgba(&self) -> (u8, u8, u8, u8) {
+        match hex::decode(&self.0[1..]) {
+            Ok(x) if x.len() == 4 => (x[0], x[1], x[2], x[3]),
+            Ok(x) if x.len() == 3 => (x[0], x[1], x[2], 0xFF),
+            _ => (0, 0, 0, 0xFF),
+        }
+    }
+
+    /// The color without its alpha channel, for outputs that do not
+    /// support transparency.
+    pub fn opaque(&self) -> Self {
+        let (r, g, b) = self.rgb();
+        HexColor::from_rgba(r, g, b, 0xFF)
+    }
+
+    /// Builds a color, leaving out the alpha channel if it is opaque.
+    pub fn from_rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        if a == 0xFF {
+            HexColor(format!("#{r:02X}{g:02X}{b:02X}"))
+        } else {
+            HexColor(format!("#{r:02X}{g:02X}{b:02X}{a:02X}"))
+        }
+    }
+
+    /// Mixes two colors channel by channel, `weight` being the share of
+    /// `self`.
+    pub fn mix(&self, other: &HexColor, weight: f64) -> Self {
+        let (r1, g1, b1, a1) = self.rgba();
+        let (r2, g2, b2, a2) = other.rgba();
+        let channel =
+            |x: u8, y: u8| (f64::from(x) * weight + f64::from(y) * (1.0 - weight)).round() as u8;
+
+        HexColor::from_rgba(
+            channel(r1, r2),
+            channel(g1, g2),
+            channel(b1, b2),
+            channel(a1, a2),
+        )
+    }
+}
+
 impl fmt::Display for HexColor {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.0)
@@ -18,9 +70,9 @@ impl FromStr for HexColor {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let re = Regex::new(r"^#[0-9A-Fa-f]{6}$")?;
+        let re = Regex::new(r"^#([0-9A-Fa-f]{6}|[0-9A-Fa-f]{8})$")?;
 
-        if re.is_match(&s) {
+        if re.is_match(s) {
             Ok(HexColor(s.to_string()))
         } else {
             Err(anyhow::anyhow!("{} is not a valid hex color", s))
@@ -42,100 +94,255 @@ impl<'de> Deserialize<'de> for HexColor {
     }
 }
 
-#[derive(Deserialize, Clone)]
+/// The named colors of the `[theme]` table. Colors that are not set fall
+/// back to the colors of the `source`, if any, and then to the default
+/// palette. Any other name adds a new color.
+#[derive(Clone)]
 pub struct Theme {
-    pub black: HexColor,
-    pub red: HexColor,
-    pub green: HexColor,
-    pub yellow: HexColor,
-    pub blue: HexColor,
-    pub magenta: HexColor,
-    pub cyan: HexColor,
-    pub white: HexColor,
-    pub purple: HexColor,
-    pub bright_black: HexColor,
-    pub bright_red: HexColor,
-    pub bright_green: HexColor,
-    pub bright_yellow: HexColor,
-    pub bright_blue: HexColor,
-    pub bright_magenta: HexColor,
-    pub bright_cyan: HexColor,
-    pub bright_white: HexColor,
-    pub bright_purple: HexColor,
-    pub background: HexColor,
-    pub foreground: HexColor,
+    colors: HashMap<String, HexColor>,
+    overrides: HashMap<String, HexColor>,
+    source: Option<ThemeSource>,
+    source_path: Option<PathBuf>,
 }
 
 impl Theme {
-    pub fn get_color(&self, col: &ThemeColor) -> &HexColor {
-        match col.0.to_lowercase().as_str() {
-            "black" => &self.black,
-            "red" => &self.red,
-            "green" => &self.green,
-            "yellow" => &self.yellow,
-            "blue" => &self.blue,
-            "magenta" => &self.magenta,
-            "cyan" => &self.cyan,
-            "white" => &self.white,
-            "purple" => &self.purple,
-            "bright_black" => &self.bright_black,
-            "bright_red" => &self.bright_red,
-            "bright_green" => &self.bright_green,
-            "bright_yellow" => &self.bright_yellow,
-            "bright_blue" => &self.bright_blue,
-            "bright_magenta" => &self.bright_magenta,
-            "bright_cyan" => &self.bright_cyan,
-            "bright_white" => &self.bright_white,
-            "bright_purple" => &self.bright_purple,
-            "background" => &self.background,
-            "foreground" => &self.foreground,
-            _ => &self.black,
-        }
+    /// Imports the colors of the `source`. Colors that are set explicitly
+    /// take precedence.
+    pub fn load_source(&mut self) -> Result<(), anyhow::Error> {
+        let Some(source) = self.source else {
+            return Ok(());
+        };
+
+        let colors = source
+            .load(self.source_path.as_deref())
+            .map_err(|x| anyhow::anyhow!("Could not load theme source {source:?}: {x}"))?;
+
+        self.colors = Theme::default().colors;
+        self.colors.extend(colors);
+        self.colors.extend(self.overrides.clone());
+
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&HexColor> {
+        self.colors.get(name)
+    }
+
+    /// Resolves a color expression against the palette, failing on the
+    /// first name that is not part of it.
+    pub fn resolve(&self, col: &ThemeColor) -> Result<HexColor, anyhow::Error> {
+        let white = HexColor("#FFFFFF".to_string());
+        let black = HexColor("#000000".to_string());
+
+        Ok(match col {
+            ThemeColor::Named(x) => match self.get(x) {
+                Some(x) => x.clone(),
+                None => {
+                    return Err(SnippetError {
+                        snippet: x.clone(),
+                        message: format!("Unknown theme color {x}"),
+                    }
+                    .into())
+                }
+            },
+            ThemeColor::Hex(x) => x.clone(),
+            ThemeColor::Lighten(x, amount) => white.mix(&self.resolve(x)?, *amount),
+            ThemeColor::Darken(x, amount) => black.mix(&self.resolve(x)?, *amount),
+            ThemeColor::Mix(x, y, weight) => self.resolve(x)?.mix(&self.resolve(y)?, *weight),
+        })
+    }
+
+    /// Returns one of the colors every theme has, e.g. `background`.
+    pub fn named(&self, name: &str) -> HexColor {
+        self.get_color(&ThemeColor::Named(name.to_string()))
+    }
+
+    /// Returns the color of a module, which `ShowBars::resolve_colors` has
+    /// already turned into a hex color at load, or of a validated expression.
+    pub fn get_color(&self, col: &ThemeColor) -> HexColor {
+        self.resolve(col)
+            .unwrap_or_else(|_| HexColor("#000000".to_string()))
     }
 }
 
 impl Default for Theme {
     fn default() -> Self {
+        let colors = [
+            ("black", "#000000"),
+            ("red", "#FF0000"),
+            ("green", "#33CC33"),
+            ("yellow", "#FFFF00"),
+            ("blue", "#3333FF"),
+            ("magenta", "#CC33CC"),
+            ("cyan", "#00CCCC"),
+            ("white", "#CCCCCC"),
+            ("purple", "#800080"),
+            ("bright_black", "#666666"),
+            ("bright_red", "#FF6666"),
+            ("bright_green", "#66FF66"),
+            ("bright_yellow", "#FFFF66"),
+            ("bright_blue", "#6666FF"),
+            ("bright_magenta", "#FF66FF"),
+            ("bright_cyan", "#66FFFF"),
+            ("bright_white", "#FFFFFF"),
+            ("bright_purple", "#BF40BF"),
+            ("background", "#000000"),
+            ("foreground", "#FFFFFF"),
+        ];
+
         Theme {
-            black: HexColor("#000000".to_string()),
-            red: HexColor("#FF0000".to_string()),
-            green: HexColor("#33CC33".to_string()),
-            yellow: HexColor("#FFFF00".to_string()),
-            blue: HexColor("#3333FF".to_string()),
-            magenta: HexColor("#CC33CC".to_string()),
-            cyan: HexColor("#00CCCC".to_string()),
-            white: HexColor("#CCCCCC".to_string()),
-            purple: HexColor("#800080".to_string()),
-
-            bright_black: HexColor("#666666".to_string()),
-            bright_red: HexColor("#FF6666".to_string()),
-            bright_green: HexColor("#66FF66".to_string()),
-            bright_yellow: HexColor("#FFFF66".to_string()),
-            bright_blue: HexColor("#6666FF".to_string()),
-            bright_magenta: HexColor("#FF66FF".to_string()),
-            bright_cyan: HexColor("#66FFFF".to_string()),
-            bright_white: HexColor("#FFFFFF".to_string()),
-            bright_purple: HexColor("#BF40BF".to_string()),
-
-            background: HexColor("#000000".to_string()),
-            foreground: HexColor("#FFFFFF".to_string()),
+            colors: colors
+                .into_iter()
+                .map(|(name, color)| (name.to_string(), HexColor(color.to_string())))
+                .collect(),
+            overrides: HashMap::new(),
+            source: None,
+            source_path: None,
         }
     }
 }
 
+#[derive(Deserialize)]
+struct ThemeTable {
+    source: Option<ThemeSource>,
+    source_path: Option<PathBuf>,
+    #[serde(flatten)]
+    colors: HashMap<String, HexColor>,
+}
+
+impl<'de> Deserialize<'de> for Theme {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let table: ThemeTable = Deserialize::deserialize(deserializer)?;
+        let mut theme = Theme {
+            source: table.source,
+            source_path: table.source_path.map(expand_home),
+            ..Theme::default()
+        };
+
+        for (name, color) in table.colors {
+            if !is_color_name(&name) {
+                return Err(de::Error::custom(format!(
+                    "{name} is not a valid color name, use letters, digits and _"
+                )));
+            }
+            theme.overrides.insert(name.to_lowercase(), color);
+        }
+        theme.colors.extend(theme.overrides.clone());
+
+        Ok(theme)
+    }
+}
+
+/// Expands a leading `~` to the home directory.
+fn expand_home(path: PathBuf) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(x), Some(home)) => home.join(x),
+        _ => path,
+    }
+}
+
+fn is_color_name(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|x| x.is_ascii_alphanumeric() || x == '_')
+}
+
+/// A color as written in a module config: a theme color name, a hex color or
+/// a color derived from others, e.g. `lighten(blue, 10%)`,
+/// `darken(background, 20%)` or `mix(red, yellow, 0.5)`.
 #[derive(Debug, Clone)]
-pub struct ThemeColor(pub String);
+pub enum ThemeColor {
+    Named(String),
+    Hex(HexColor),
+    Lighten(Box<ThemeColor>, f64),
+    Darken(Box<ThemeColor>, f64),
+    Mix(Box<ThemeColor>, Box<ThemeColor>, f64),
+}
+
+impl ThemeColor {
+    fn parse(s: &str) -> Result<Self, anyhow::Error> {
+        let s = s.trim();
+
+        if s.starts_with('#') {
+            return Ok(ThemeColor::Hex(HexColor::from_str(s)?));
+        }
+
+        let Some((function, args)) = s.strip_suffix(')').and_then(|x| x.split_once('(')) else {
+            if !is_color_name(s) {
+                return Err(anyhow::anyhow!("{} is not a valid theme color", s));
+            }
+            return Ok(ThemeColor::Named(s.to_lowercase()));
+        };
+
+        let args = split_args(args);
+        let color = |index: usize| ThemeColor::parse(args[index]).map(Box::new);
+
+        match (function.trim(), args.len()) {
+            ("lighten", 2) => Ok(ThemeColor::Lighten(color(0)?, parse_amount(args[1])?)),
+            ("darken", 2) => Ok(ThemeColor::Darken(color(0)?, parse_amount(args[1])?)),
+            ("mix", 2) => Ok(ThemeColor::Mix(color(0)?, color(1)?, 0.5)),
+            ("mix", 3) => Ok(ThemeColor::Mix(
+                color(0)?,
+                color(1)?,
+                parse_amount(args[2])?,
+            )),
+            ("lighten" | "darken", _) => Err(anyhow::anyhow!(
+                "{function} takes a color and an amount, e.g. {function}(blue, 10%)"
+            )),
+            ("mix", _) => Err(anyhow::anyhow!(
+                "mix takes two colors and an optional weight, e.g. mix(red, yellow, 0.5)"
+            )),
+            _ => Err(anyhow::anyhow!("Unknown color function {function}")),
+        }
+    }
+}
+
+/// Splits the arguments of a color function on the commas that are not
+/// inside a nested call.
+fn split_args(s: &str) -> Vec<&str> {
+    let mut args = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (pos, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(&s[start..pos]);
+                start = pos + 1;
+            }
+            _ => {}
+        }
+    }
+    args.push(&s[start..]);
+
+    args
+}
+
+/// Parses `10%` or `0.1` into a fraction between 0 and 1.
+fn parse_amount(s: &str) -> Result<f64, anyhow::Error> {
+    let s = s.trim();
+    let amount = match s.strip_suffix('%') {
+        Some(x) => x.trim().parse::<f64>().map(|x| x / 100.0),
+        None => s.parse::<f64>(),
+    };
+
+    match amount {
+        Ok(x) if (0.0..=1.0).contains(&x) => Ok(x),
+        _ => Err(anyhow::anyhow!(
+            "{} is not a valid amount, expected 0% to 100% or 0 to 1",
+            s
+        )),
+    }
+}
 
 impl FromStr for ThemeColor {
     type Err = anyhow::Error;
+
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let re =
-            Regex::new(r"^(black|red|green|yellow|blue|magenta|cyan|white|purple|bright_black|bright_red|bright_green|bright_yellow|bright_blue|bright_magenta|bright_cyan|bright_white|bright_purple|background|foreground)$").unwrap();
-        if re.is_match(&s) {
-            Ok(ThemeColor(s.to_string()))
-        } else {
-            Err(anyhow::anyhow!("{} is not a valid theme color", s))
-        }
+        ThemeColor::parse(s).map_err(|x| anyhow::anyhow!("Invalid color \"{s}\": {x}"))
     }
 }
 
@@ -152,3 +359,48 @@ impl<'de> Deserialize<'de> for ThemeColor {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(color: &str) -> String {
+        Theme::default()
+            .resolve(&ThemeColor::from_str(color).unwrap())
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn lightens_and_darkens() {
+        assert_eq!(resolve("lighten(blue, 10%)"), "#4747FF");
+        assert_eq!(resolve("darken(blue, 0.2)"), "#2929CC");
+        assert_eq!(resolve("lighten(#123456, 0%)"), "#123456");
+        assert_eq!(resolve("darken(#123456, 100%)"), "#000000");
+    }
+
+    #[test]
+    fn mixes_colors() {
+        assert_eq!(resolve("mix(red, yellow)"), "#FF8000");
+        assert_eq!(resolve("mix(red, yellow, 75%)"), "#FF4000");
+        assert_eq!(resolve("mix(#00000000, #FFFFFF)"), "#80808080");
+        assert_eq!(resolve("lighten(mix(red, blue), 50%)"), "#CC8DC0");
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        for color in [
+            "lighten(blue)",
+            "mix(red)",
+            "darken(blue, 150%)",
+            "shade(blue, 10%)",
+            "#12345",
+            "light blue",
+        ] {
+            assert!(ThemeColor::from_str(color).is_err(), "{color}");
+        }
+
+        let unknown = ThemeColor::from_str("mix(red, teal)").unwrap();
+        assert!(Theme::default().resolve(&unknown).is_err());
+    }
+}
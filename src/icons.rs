@@ -1,56 +1,252 @@
-static NO_WIFI_ICON: &str = "󰖪";
-static WIFI_ICON: &str = "󰖩";
+use serde::de;
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::str::FromStr;
 
-static VOLUME_ICONS: [&str; 3] = ["󰕿", "󰖀", "󰕾"];
+use crate::format::SnippetError;
 
-static BRIGHTNESS_ICONS: [&str; 3] = ["󰃞", "󰃟", "󰃠"];
+/// The glyphs the `{icon}` fields are drawn from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IconSet {
+    /// Nerd Font glyphs, needs a patched font
+    Nerdfont,
+    /// Unicode emoji
+    Emoji,
+    /// Plain ASCII labels
+    Ascii,
+    /// No icons at all
+    None,
+}
 
-static VOLUME_MUTED_ICON: &str = "󰸈";
-static KEYBOARD_ICON: &str = "󰥻";
-static CLOCK_ICON: &str = "󰥔";
+impl IconSet {
+    /// The default icons of a module's ramp, e.g. `charging` of `battery`.
+    fn icons(self, kind: &str, name: &str) -> &'static [&'static str] {
+        match (self, kind, name) {
+            (IconSet::None, _, _) => &[""],
 
-static BATTERY_ICONS: [&str; 10] = ["󰁺", "󰁻", "󰁼", "󰁽", "󰁾", "󰁿", "󰂀", "󰂁", "󰂂", "󰁹"];
-static CHARGING_ICONS: [&str; 10] = ["󰢜", "󰂆", "󰂇", "󰂈", "󰢝", "󰂉", "󰢞", "󰂊", "󰂋", "󰂅"];
+            (IconSet::Nerdfont, "battery", "discharging") => {
+                &["󰁺", "󰁻", "󰁼", "󰁽", "󰁾", "󰁿", "󰂀", "󰂁", "󰂂", "󰁹"]
+            }
+            (IconSet::Nerdfont, "battery", "charging") => {
+                &["󰢜", "󰂆", "󰂇", "󰂈", "󰢝", "󰂉", "󰢞", "󰂊", "󰂋", "󰂅"]
+            }
+            (IconSet::Nerdfont, "audio", "volume") => &["󰕿", "󰖀", "󰕾"],
+            (IconSet::Nerdfont, "audio", "muted") => &["󰸈"],
+            (IconSet::Nerdfont, "microphone", "volume") => &["󰍬"],
+            (IconSet::Nerdfont, "microphone", "muted") => &["󰍭"],
+            (IconSet::Nerdfont, "brightness", "brightness") => &["󰃞", "󰃟", "󰃠"],
+            (IconSet::Nerdfont, "network", "ethernet") => &["󰈀"],
+            (IconSet::Nerdfont, "network", "wifi") => &["󰤯", "󰤟", "󰤢", "󰤥", "󰤨"],
+            (IconSet::Nerdfont, "network", "vpn") => &["󰖂"],
+            (IconSet::Nerdfont, "network", "disconnected") => &["󰌙"],
+            (IconSet::Nerdfont, "bandwidth", "bandwidth") => &["󰓅"],
+            (IconSet::Nerdfont, "vpn", "connected") => &["󰦝"],
+            (IconSet::Nerdfont, "vpn", "disconnected") => &["󰦞"],
 
-pub fn get_battery_icon(battery_percent: f32, is_charging: bool) -> &'static str {
-    let index = if battery_percent >= 100.0 {
-        9
-    } else {
-        (battery_percent / 10.0) as usize
-    };
-    if is_charging {
-        CHARGING_ICONS[index]
-    } else {
-        BATTERY_ICONS[index]
+            (IconSet::Emoji, "battery", "discharging") => &["🪫", "🔋"],
+            (IconSet::Emoji, "battery", "charging") => &["⚡"],
+            (IconSet::Emoji, "audio", "volume") => &["🔈", "🔉", "🔊"],
+            (IconSet::Emoji, "audio", "muted") => &["🔇"],
+            (IconSet::Emoji, "microphone", "volume") => &["🎤"],
+            (IconSet::Emoji, "microphone", "muted") => &["🔇"],
+            (IconSet::Emoji, "brightness", "brightness") => &["🔅", "🔆"],
+            (IconSet::Emoji, "network", "ethernet") => &["🔌"],
+            (IconSet::Emoji, "network", "wifi") => &["📶"],
+            (IconSet::Emoji, "network", "vpn") => &["🔒"],
+            (IconSet::Emoji, "network", "disconnected") => &["🚫"],
+            (IconSet::Emoji, "bandwidth", "bandwidth") => &["🌐"],
+            (IconSet::Emoji, "vpn", "connected") => &["🔒"],
+            (IconSet::Emoji, "vpn", "disconnected") => &["🔓"],
+
+            (IconSet::Ascii, "battery", "discharging") => &["BAT"],
+            (IconSet::Ascii, "battery", "charging") => &["CHR"],
+            (IconSet::Ascii, "audio", "volume") => &["VOL"],
+            (IconSet::Ascii, "audio", "muted") => &["MUT"],
+            (IconSet::Ascii, "microphone", "volume") => &["MIC"],
+            (IconSet::Ascii, "microphone", "muted") => &["M--"],
+            (IconSet::Ascii, "brightness", "brightness") => &["BRI"],
+            (IconSet::Ascii, "network", "ethernet") => &["ETH"],
+            (IconSet::Ascii, "network", "wifi") => &["WLAN"],
+            (IconSet::Ascii, "network", "vpn") => &["VPN"],
+            (IconSet::Ascii, "network", "disconnected") => &["---"],
+            (IconSet::Ascii, "bandwidth", "bandwidth") => &["BW"],
+            (IconSet::Ascii, "vpn", "connected") => &["VPN"],
+            (IconSet::Ascii, "vpn", "disconnected") => &["---"],
+
+            _ => &[""],
+        }
     }
 }
 
-pub fn get_wifi_icon(network_ssid: &str) -> &'static str {
-    if network_ssid == "" {
-        NO_WIFI_ICON
-    } else {
-        WIFI_ICON
+impl FromStr for IconSet {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nerdfont" => Ok(IconSet::Nerdfont),
+            "emoji" => Ok(IconSet::Emoji),
+            "ascii" => Ok(IconSet::Ascii),
+            "none" => Ok(IconSet::None),
+            _ => Err(anyhow::anyhow!("{} is not a valid icon set", s)),
+        }
     }
 }
 
-pub fn get_volume_icon(volume_percent: u32, is_muted: bool) -> &'static str {
-    if is_muted {
-        return VOLUME_MUTED_ICON;
+impl<'de> Deserialize<'de> for IconSet {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s: String = Deserialize::deserialize(deserializer)?;
+
+        match IconSet::from_str(&s) {
+            Ok(x) => Ok(x),
+            Err(x) => Err(de::Error::custom(x)),
+        }
     }
+}
 
-    let index = match volume_percent {
-        0..=33 => 0,
-        34..=66 => 1,
-        _ => 2,
+/// Picks the icon of a ramp for `value`. Without thresholds the range from
+/// 0 to 100 is split into equally sized buckets, otherwise `icons[i]` is
+/// shown from `thresholds[i]` on.
+fn pick<'a, S: AsRef<str>>(icons: &'a [S], thresholds: &[f64], value: f64) -> &'a str {
+    let index = if thresholds.is_empty() {
+        ((value / 100.0 * icons.len() as f64) as usize).min(icons.len().saturating_sub(1))
+    } else {
+        thresholds.iter().rposition(|x| value >= *x).unwrap_or(0)
     };
-    VOLUME_ICONS[index]
+
+    icons.get(index).map_or("", |x| x.as_ref())
 }
 
-pub fn get_brightness_icon(brightness_percent: u32) -> &'static str {
-    let index = match brightness_percent {
-        0..=33 => 0,
-        34..=66 => 1,
-        _ => 2,
-    };
-    BRIGHTNESS_ICONS[index]
+/// A module's own icons for one of its ramps, written as a single icon, a
+/// list of icons or a table with `icons` and `thresholds`.
+#[derive(Clone)]
+pub struct Ramp {
+    icons: Vec<String>,
+    thresholds: Vec<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RampConfig {
+    Icon(String),
+    Icons(Vec<String>),
+    Table {
+        icons: Vec<String>,
+        #[serde(default)]
+        thresholds: Vec<f64>,
+    },
+}
+
+impl<'de> Deserialize<'de> for Ramp {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (icons, thresholds) = match RampConfig::deserialize(deserializer)? {
+            RampConfig::Icon(x) => (vec![x], vec![]),
+            RampConfig::Icons(x) => (x, vec![]),
+            RampConfig::Table { icons, thresholds } => (icons, thresholds),
+        };
+
+        if icons.is_empty() {
+            return Err(de::Error::custom("An icon ramp needs at least one icon"));
+        }
+        if !thresholds.is_empty() && thresholds.len() != icons.len() {
+            return Err(de::Error::custom(
+                "An icon ramp needs one threshold per icon",
+            ));
+        }
+        if thresholds.windows(2).any(|x| x[0] > x[1]) {
+            return Err(de::Error::custom(
+                "The thresholds of an icon ramp must be ascending",
+            ));
+        }
+
+        Ok(Ramp { icons, thresholds })
+    }
+}
+
+/// The `icons` table of a module, overriding ramps of the icon set.
+#[derive(Deserialize, Clone, Default)]
+pub struct Icons(HashMap<String, Ramp>);
+
+impl Icons {
+    /// Returns the icon of the ramp `name` of a module of type `kind` for
+    /// `value`, preferring the module's own icons over the icon set.
+    pub fn get(&self, set: IconSet, kind: &str, name: &str, value: f64) -> &str {
+        match self.0.get(name) {
+            Some(x) => pick(&x.icons, &x.thresholds, value),
+            None => pick(set.icons(kind, name), &[], value),
+        }
+    }
+
+    /// Fails on the first ramp that is not one of `names`.
+    pub fn validate(&self, names: &[&str]) -> Result<(), anyhow::Error> {
+        match self.0.keys().find(|x| !names.contains(&x.as_str())) {
+            Some(x) => Err(SnippetError {
+                snippet: x.clone(),
+                message: format!("Unknown icons {x}, expected one of {}", names.join(", ")),
+            }
+            .into()),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RAMP: &[&str] = &["a", "b", "c", "d"];
+
+    #[test]
+    fn picks_the_ends_of_the_ramp() {
+        assert_eq!(pick(RAMP, &[], 0.0), "a");
+        assert_eq!(pick(RAMP, &[], 24.9), "a");
+        assert_eq!(pick(RAMP, &[], 25.0), "b");
+        assert_eq!(pick(RAMP, &[], 99.9), "d");
+        assert_eq!(pick(RAMP, &[], 100.0), "d");
+    }
+
+    #[test]
+    fn clamps_values_out_of_range() {
+        assert_eq!(pick(RAMP, &[], -10.0), "a");
+        assert_eq!(pick(RAMP, &[], 150.0), "d");
+        assert_eq!(pick(RAMP, &[], f64::NAN), "a");
+        assert_eq!(pick::<&str>(&[], &[], 50.0), "");
+    }
+
+    #[test]
+    fn picks_by_thresholds() {
+        let thresholds = [0.0, 10.0, 50.0, 90.0];
+
+        assert_eq!(pick(RAMP, &thresholds, -5.0), "a");
+        assert_eq!(pick(RAMP, &thresholds, 10.0), "b");
+        assert_eq!(pick(RAMP, &thresholds, 89.0), "c");
+        assert_eq!(pick(RAMP, &thresholds, 100.0), "d");
+    }
+
+    #[test]
+    fn prefers_module_icons() {
+        let icons: Icons = toml::from_str("muted = \"M\"\nvolume = [\"1\", \"2\"]\n").unwrap();
+
+        assert_eq!(icons.get(IconSet::Ascii, "audio", "muted", 0.0), "M");
+        assert_eq!(icons.get(IconSet::Ascii, "audio", "volume", 100.0), "2");
+        assert_eq!(icons.get(IconSet::Ascii, "battery", "charging", 0.0), "CHR");
+        assert!(toml::from_str::<Icons>("volume = []\n").is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_ramps() {
+        let icons: Icons = toml::from_str("volme = \"V\"\n").unwrap();
+        let error = icons.validate(&["volume", "muted"]).unwrap_err();
+
+        assert_eq!(
+            error.downcast_ref::<SnippetError>().unwrap().snippet,
+            "volme"
+        );
+        assert!(icons.validate(&["volme"]).is_ok());
+    }
 }
@@ -1,31 +1,65 @@
+mod alsa;
+mod check;
 mod config;
+mod format;
 mod hexcolor;
 mod icons;
+mod markup;
+mod netlink;
+mod output;
 mod property;
+mod pulse;
+mod reload;
+mod state;
+mod sysfs;
+mod themesource;
 
 use crate::config::{read_config, RsbrConfig};
 use anyhow::Result;
 use getopts::Options;
-use property::audio::AudioProperty;
-use property::battery::BatteryProperty;
-use property::brightness::BrightnessProperty;
-use property::datetime::DatetimeProperty;
-use property::network::NetworkProperty;
-use property::{ShowBar, ShowBars};
+use markup::Markup;
+use output::OutputKind;
+use property::ShowBars;
 use std::env;
-use std::path::PathBuf;
-use tokio::time::{sleep, Duration};
-use x11rb::connection::Connection;
-use x11rb::protocol::xproto::*;
-use x11rb::rust_connection::RustConnection;
-use x11rb::wrapper::ConnectionExt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::time::sleep_until;
 
 fn usage(progname: &str, opts: getopts::Options) {
-    let brief = format!("Usage: {progname} [options]");
+    let brief = format!("Usage: {progname} [options]\n       {progname} check [options]");
     let usage = opts.usage(&brief);
     eprint!("{usage}");
 }
 
+fn load(config_path: &Path) -> Result<(Arc<RsbrConfig>, ShowBars), anyhow::Error> {
+    let config = read_config(config_path)?;
+    // Modules that are not placed in `format` are validated as well, as an
+    // invalid name is a likely reason for it.
+    ShowBars::new(property::from_config(&config)).validate(&config)?;
+    let mut attributes = ShowBars::new(property::placed(&config));
+    attributes.resolve_colors(&config.theme);
+
+    Ok((Arc::new(config), attributes))
+}
+
+/// Condenses an error, e.g. a TOML error with a source excerpt, into a
+/// single line that fits into the bar.
+fn error_line(error: &anyhow::Error) -> String {
+    let message = format!("{error:#}");
+    let lines: Vec<&str> = message
+        .lines()
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty())
+        .collect();
+
+    match lines.as_slice() {
+        [] => "Unknown error".to_string(),
+        [x] => x.to_string(),
+        [first, .., last] => format!("{first}: {last}"),
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
     let mut default_config_path = match dirs::home_dir() {
@@ -48,6 +82,23 @@ async fn main() -> Result<(), anyhow::Error> {
         "toml config file",
         default_config_path.to_str().unwrap_or(""),
     );
+    opts.optopt(
+        "o",
+        "output",
+        "where to write the bar to",
+        "x11|stdout|i3bar",
+    );
+    opts.optopt(
+        "m",
+        "markup",
+        "color escapes of text outputs",
+        "status2d|lemonbar|tmux|pango|ansi|plain",
+    );
+    opts.optflag(
+        "1",
+        "once",
+        "Print the bar once and exit, implies --output stdout",
+    );
 
     let matches = match opts.parse(&args[1..]) {
         Ok(x) => x,
@@ -68,40 +119,107 @@ async fn main() -> Result<(), anyhow::Error> {
         None => default_config_path,
     };
 
-    let config = match read_config(&config_path) {
+    match matches.free.first().map(|x| x.as_str()) {
+        Some("check") if matches.free.len() == 1 => {
+            if !check::check(&config_path) {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(x) => {
+            let x = anyhow::anyhow!("Unexpected argument {x}");
+            eprintln!("{x}");
+            usage(&progname, opts);
+            return Err(x);
+        }
+        None => {}
+    }
+
+    let (mut config, mut attributes) = match load(&config_path) {
         Ok(x) => x,
         Err(x) => {
-            eprintln!("Error while loading config file: {x}");
-            RsbrConfig::default()
+            eprintln!("Error while loading config file: {x:#}");
+            let config = RsbrConfig::default();
+            let mut attributes = ShowBars::new(property::placed(&config));
+            attributes.resolve_colors(&config.theme);
+            if config_path.exists() {
+                attributes.set_error(&config, &error_line(&x));
+            }
+            (Arc::new(config), attributes)
         }
     };
 
-    let (conn, screen_num) = RustConnection::connect(None)?;
-    let screen = &conn.setup().roots[screen_num];
+    let once = matches.opt_present("1");
+    let output_kind = match matches.opt_str("o") {
+        Some(x) => match OutputKind::from_str(&x) {
+            Ok(x) => x,
+            Err(x) => {
+                eprintln!("{x}");
+                usage(&progname, opts);
+                return Err(x);
+            }
+        },
+        None if once => OutputKind::Stdout,
+        None => config.output,
+    };
+
+    let markup = match matches.opt_str("m") {
+        Some(x) => match Markup::from_str(&x) {
+            Ok(x) => x,
+            Err(x) => {
+                eprintln!("{x}");
+                usage(&progname, opts);
+                return Err(x);
+            }
+        },
+        None => config.markup,
+    };
+
+    let mut output = output_kind.create(markup)?;
 
-    let root_window = screen.root;
+    if once {
+        attributes.render_all(&config).await;
+        return output.write(&config.format, &attributes);
+    }
+
+    let mut signals = attributes.listen();
 
-    let properties: Vec<Box<dyn ShowBar>> = vec![
-        Box::new(BatteryProperty),
-        Box::new(BrightnessProperty),
-        Box::new(DatetimeProperty),
-        Box::new(AudioProperty),
-        Box::new(NetworkProperty),
-    ];
-    let attributes = ShowBars::new(properties);
+    let mut clicks = output.clicks();
+    let mut reloads = reload::watch(&config_path);
+    let mut redraw = true;
 
     loop {
-        let root_name = attributes.process(config.clone()).await;
-
-        conn.change_property8(
-            PropMode::REPLACE,
-            root_window,
-            AtomEnum::WM_NAME,
-            AtomEnum::STRING,
-            root_name.as_bytes(),
-        )?
-        .check()?;
-
-        sleep(Duration::from_secs(1)).await;
+        attributes.render(&config);
+
+        if redraw {
+            output.write(&config.format, &attributes)?;
+            redraw = false;
+        }
+
+        let next_update = attributes.next_update();
+        let next_update = async {
+            match next_update {
+                Some(x) => sleep_until(x).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            _ = next_update => {}
+            changed = attributes.receive(&config) => redraw = changed,
+            Some(index) = signals.recv() => attributes.refresh(index),
+            Some(click) = clicks.recv() => attributes.click(&click.name, click.button),
+            Some(()) = reloads.recv() => match load(&config_path) {
+                Ok(x) => {
+                    (config, attributes) = x;
+                    signals = attributes.listen();
+                }
+                Err(x) => {
+                    eprintln!("Error while reloading config file: {x}");
+                    attributes.set_error(&config, &error_line(&x));
+                    redraw = true;
+                }
+            },
+        }
     }
 }
@@ -0,0 +1,202 @@
+use serde::de;
+use serde::{Deserialize, Deserializer};
+use std::str::FromStr;
+
+use crate::format::FormatString;
+use crate::hexcolor::HexColor;
+use crate::property::{Block, ShowBars};
+
+/// The color escapes a text output wraps every block in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Markup {
+    /// dwm status2d patch: `^c#rrggbb^^b#rrggbb^`
+    Status2d,
+    /// lemonbar and polybar: `%{F#rrggbb}%{B#rrggbb}`
+    Lemonbar,
+    /// tmux status line: `#[fg=#rrggbb,bg=#rrggbb]`
+    Tmux,
+    /// Pango markup as used by swaybar and waybar
+    Pango,
+    /// ANSI truecolor terminal escapes
+    Ansi,
+    /// No colors at all
+    Plain,
+}
+
+impl Markup {
+    /// Renders the bar as a single line, filling every placeholder of
+    /// `format` with the colored block of its module. A pending error is
+    /// shown in front of the bar.
+    pub fn render(self, format: &FormatString, bar: &ShowBars) -> String {
+        let line = format.render_escaped(
+            |name| bar.get(name).map(|(_, block)| self.block(block)),
+            |text| self.escape(text),
+        );
+
+        match bar.error() {
+            Some(error) => self.block(error) + &line,
+            None => line,
+        }
+    }
+
+    /// Renders a block with its colors. A module hides itself with an empty
+    /// block.
+    fn block(self, block: &Block) -> String {
+        let fg = &block.fgcolor;
+        let bg = &block.bgcolor;
+        let text = self.escape(&block.full_text);
+
+        if text.is_empty() {
+            return String::new();
+        }
+
+        match self {
+            Markup::Status2d => format!("^c{}^^b{}^ {text}", fg.opaque(), bg.opaque()),
+            Markup::Lemonbar => format!(
+                "%{{F{}}}%{{B{}}} {text}%{{F-}}%{{B-}}",
+                lemonbar_color(fg),
+                lemonbar_color(bg)
+            ),
+            Markup::Tmux => format!("#[fg={},bg={}] {text}#[default]", fg.opaque(), bg.opaque()),
+            Markup::Pango => format!(
+                "<span {}{}> {text}</span>",
+                pango_color("foreground", "fgalpha", fg),
+                pango_color(" background", "bgalpha", bg)
+            ),
+            Markup::Ansi => {
+                let (r, g, b) = fg.rgb();
+                let (br, bg, bb) = bg.rgb();
+                format!("\x1b[38;2;{r};{g};{b}m\x1b[48;2;{br};{bg};{bb}m {text}\x1b[0m")
+            }
+            Markup::Plain => format!(" {text}"),
+        }
+    }
+
+    /// Escapes the characters of `text` that the dialect would take as
+    /// markup.
+    fn escape(self, text: &str) -> String {
+        match self {
+            // status2d has no escape for ^, which starts a command, so it is
+            // replaced by a lookalike.
+            Markup::Status2d => text.replace('^', "\u{2C6}"),
+            Markup::Lemonbar => text.replace('%', "%%"),
+            Markup::Tmux => text.replace('#', "##"),
+            Markup::Pango => text
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;"),
+            Markup::Ansi | Markup::Plain => text.to_string(),
+        }
+    }
+}
+
+/// lemonbar expects the alpha channel in front, as `#AARRGGBB`.
+fn lemonbar_color(color: &HexColor) -> String {
+    match color.rgba() {
+        (r, g, b, 0xFF) => format!("#{r:02X}{g:02X}{b:02X}"),
+        (r, g, b, a) => format!("#{a:02X}{r:02X}{g:02X}{b:02X}"),
+    }
+}
+
+/// Pango takes the alpha channel as a separate attribute, from 1 to 65536.
+fn pango_color(attribute: &str, alpha_attribute: &str, color: &HexColor) -> String {
+    match color.rgba() {
+        (_, _, _, 0xFF) => format!("{attribute}=\"{}\"", color.opaque()),
+        (_, _, _, a) => format!(
+            "{attribute}=\"{}\" {alpha_attribute}=\"{}\"",
+            color.opaque(),
+            (u32::from(a) * 257).max(1)
+        ),
+    }
+}
+
+impl FromStr for Markup {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "status2d" => Ok(Markup::Status2d),
+            "lemonbar" | "polybar" => Ok(Markup::Lemonbar),
+            "tmux" => Ok(Markup::Tmux),
+            "pango" => Ok(Markup::Pango),
+            "ansi" => Ok(Markup::Ansi),
+            "plain" => Ok(Markup::Plain),
+            _ => Err(anyhow::anyhow!("{} is not a valid markup", s)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Markup {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s: String = Deserialize::deserialize(deserializer)?;
+
+        match Markup::from_str(&s) {
+            Ok(x) => Ok(x),
+            Err(x) => Err(de::Error::custom(x)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(text: &str, fgcolor: &str, bgcolor: &str) -> Block {
+        Block {
+            full_text: text.to_string(),
+            fgcolor: HexColor(fgcolor.to_string()),
+            bgcolor: HexColor(bgcolor.to_string()),
+        }
+    }
+
+    #[test]
+    fn converts_alpha_per_dialect() {
+        let block = block("x", "#112233", "#44556680");
+
+        assert_eq!(Markup::Status2d.block(&block), "^c#112233^^b#445566^ x");
+        assert_eq!(
+            Markup::Lemonbar.block(&block),
+            "%{F#112233}%{B#80445566} x%{F-}%{B-}"
+        );
+        assert_eq!(
+            Markup::Tmux.block(&block),
+            "#[fg=#112233,bg=#445566] x#[default]"
+        );
+        assert_eq!(
+            Markup::Pango.block(&block),
+            "<span foreground=\"#112233\" background=\"#445566\" bgalpha=\"32896\"> x</span>"
+        );
+    }
+
+    #[test]
+    fn escapes_block_text() {
+        let block = block("a^b %1 #2 <&>", "#FFFFFF", "#000000");
+
+        assert_eq!(
+            Markup::Status2d.block(&block),
+            "^c#FFFFFF^^b#000000^ a\u{2C6}b %1 #2 <&>"
+        );
+        assert!(Markup::Lemonbar.block(&block).contains(" a^b %%1 #2 <&>"));
+        assert!(Markup::Tmux.block(&block).contains(" a^b %1 ##2 <&>"));
+        assert!(Markup::Pango
+            .block(&block)
+            .contains(" a^b %1 #2 &lt;&amp;&gt;"));
+    }
+
+    #[test]
+    fn escapes_literal_text() {
+        let format = FormatString::from_str("<{a}> & {b}").unwrap();
+        let line = format.render_escaped(|x| Some(format!("<{x}/>")), |x| Markup::Pango.escape(x));
+
+        assert_eq!(line, "&lt;<a/>&gt; &amp; <b/>");
+    }
+
+    #[test]
+    fn hides_empty_blocks() {
+        assert_eq!(Markup::Pango.block(&block("", "#FFFFFF", "#000000")), "");
+    }
+}
@@ -0,0 +1,621 @@
+//! A minimal netlink client for the addresses and routes of network
+//! interfaces (rtnetlink), the state of wireless interfaces (nl80211) and
+//! WireGuard tunnels.
+
+use std::io;
+use std::mem;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const NLMSG_HEADER_LENGTH: usize = 16;
+const NLMSG_ERROR: u16 = 2;
+const NLMSG_DONE: u16 = 3;
+const NLM_F_REQUEST: u16 = 0x1;
+const NLM_F_MULTI: u16 = 0x2;
+const NLM_F_DUMP: u16 = 0x300;
+
+const RTM_GETADDR: u16 = 22;
+const IFA_ADDRESS: u16 = 1;
+const IFA_LOCAL: u16 = 2;
+const RT_SCOPE_UNIVERSE: u8 = 0;
+const RTM_GETROUTE: u16 = 26;
+const RTA_OIF: u16 = 4;
+const RTA_PRIORITY: u16 = 6;
+const RTA_TABLE: u16 = 15;
+const RT_TABLE_MAIN: u32 = 254;
+const RTN_UNICAST: u8 = 1;
+
+const GENL_ID_CTRL: u16 = 0x10;
+const CTRL_CMD_GETFAMILY: u8 = 3;
+const CTRL_ATTR_FAMILY_ID: u16 = 1;
+const CTRL_ATTR_FAMILY_NAME: u16 = 2;
+
+const NL80211_CMD_GET_INTERFACE: u8 = 5;
+const NL80211_CMD_GET_STATION: u8 = 17;
+const NL80211_ATTR_IFINDEX: u16 = 3;
+const NL80211_ATTR_STA_INFO: u16 = 21;
+const NL80211_ATTR_WIPHY_FREQ: u16 = 38;
+const NL80211_ATTR_SSID: u16 = 52;
+const NL80211_STA_INFO_SIGNAL: u16 = 7;
+const NL80211_STA_INFO_TX_BITRATE: u16 = 8;
+const NL80211_RATE_INFO_BITRATE: u16 = 1;
+const NL80211_RATE_INFO_BITRATE32: u16 = 5;
+
+const WG_CMD_GET_DEVICE: u8 = 0;
+const WGDEVICE_A_IFINDEX: u16 = 1;
+const WGDEVICE_A_PEERS: u16 = 8;
+const WGPEER_A_LAST_HANDSHAKE_TIME: u16 = 6;
+
+/// A received message without its header.
+struct Message {
+    kind: u16,
+    payload: Vec<u8>,
+}
+
+struct Socket {
+    fd: OwnedFd,
+    sequence: u32,
+}
+
+impl Socket {
+    fn open(protocol: i32) -> io::Result<Self> {
+        // SAFETY: plain system call, the result is checked below.
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+                protocol,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            // SAFETY: `fd` was just opened and is owned by nobody else.
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+            sequence: 0,
+        })
+    }
+
+    /// Sends a request and collects the messages of its reply, which is
+    /// either a single message or a dump ended by `NLMSG_DONE`.
+    fn request(&mut self, kind: u16, flags: u16, payload: &[u8]) -> io::Result<Vec<Message>> {
+        self.sequence = self.sequence.wrapping_add(1);
+
+        let mut packet = Vec::with_capacity(NLMSG_HEADER_LENGTH + payload.len());
+        packet.extend_from_slice(&((NLMSG_HEADER_LENGTH + payload.len()) as u32).to_ne_bytes());
+        packet.extend_from_slice(&kind.to_ne_bytes());
+        packet.extend_from_slice(&(flags | NLM_F_REQUEST).to_ne_bytes());
+        packet.extend_from_slice(&self.sequence.to_ne_bytes());
+        packet.extend_from_slice(&0u32.to_ne_bytes());
+        packet.extend_from_slice(payload);
+
+        // SAFETY: the address is a zeroed sockaddr_nl addressed to the
+        // kernel, and the buffer outlives the call.
+        let sent = unsafe {
+            let mut address: libc::sockaddr_nl = mem::zeroed();
+            address.nl_family = libc::AF_NETLINK as u16;
+            libc::sendto(
+                self.fd.as_raw_fd(),
+                packet.as_ptr().cast(),
+                packet.len(),
+                0,
+                (&address as *const libc::sockaddr_nl).cast(),
+                mem::size_of::<libc::sockaddr_nl>() as u32,
+            )
+        };
+        if sent < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut messages = vec![];
+        let mut buffer = vec![0u8; 32768];
+
+        loop {
+            // SAFETY: the buffer is valid for its whole length.
+            let length = unsafe {
+                libc::recv(
+                    self.fd.as_raw_fd(),
+                    buffer.as_mut_ptr().cast(),
+                    buffer.len(),
+                    0,
+                )
+            };
+            if length < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let mut data = &buffer[..length as usize];
+            while data.len() >= NLMSG_HEADER_LENGTH {
+                let length = u32::from_ne_bytes([data[0], data[1], data[2], data[3]]) as usize;
+                let kind = u16::from_ne_bytes([data[4], data[5]]);
+                let flags = u16::from_ne_bytes([data[6], data[7]]);
+                let sequence = u32::from_ne_bytes([data[8], data[9], data[10], data[11]]);
+                if length < NLMSG_HEADER_LENGTH || length > data.len() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Truncated netlink message",
+                    ));
+                }
+
+                let payload = &data[NLMSG_HEADER_LENGTH..length];
+                data = &data[align(length).min(data.len())..];
+
+                if sequence != self.sequence {
+                    continue;
+                }
+
+                match kind {
+                    NLMSG_DONE => return Ok(messages),
+                    NLMSG_ERROR => {
+                        let code = payload
+                            .get(..4)
+                            .map_or(0, |x| i32::from_ne_bytes([x[0], x[1], x[2], x[3]]));
+                        if code != 0 {
+                            return Err(io::Error::from_raw_os_error(-code));
+                        }
+                        return Ok(messages);
+                    }
+                    _ => messages.push(Message {
+                        kind,
+                        payload: payload.to_vec(),
+                    }),
+                }
+
+                if flags & NLM_F_MULTI == 0 {
+                    return Ok(messages);
+                }
+            }
+        }
+    }
+}
+
+fn align(length: usize) -> usize {
+    (length + 3) & !3
+}
+
+/// Splits a buffer of netlink attributes into their types and values.
+fn attributes(mut data: &[u8]) -> Vec<(u16, &[u8])> {
+    let mut attributes = vec![];
+
+    while data.len() >= 4 {
+        let length = u16::from_ne_bytes([data[0], data[1]]) as usize;
+        // The upper bits are the nested and byte order flags.
+        let kind = u16::from_ne_bytes([data[2], data[3]]) & 0x3FFF;
+        if length < 4 || length > data.len() {
+            break;
+        }
+
+        attributes.push((kind, &data[4..length]));
+        data = &data[align(length).min(data.len())..];
+    }
+
+    attributes
+}
+
+fn find<'a>(attributes: &[(u16, &'a [u8])], kind: u16) -> Option<&'a [u8]> {
+    attributes
+        .iter()
+        .find(|(x, _)| *x == kind)
+        .map(|(_, value)| *value)
+}
+
+fn find_u32(attributes: &[(u16, &[u8])], kind: u16) -> Option<u32> {
+    Some(u32::from_ne_bytes(find(attributes, kind)?.try_into().ok()?))
+}
+
+fn attribute(kind: u16, value: &[u8]) -> Vec<u8> {
+    let mut attribute = Vec::with_capacity(align(4 + value.len()));
+    attribute.extend_from_slice(&((4 + value.len()) as u16).to_ne_bytes());
+    attribute.extend_from_slice(&kind.to_ne_bytes());
+    attribute.extend_from_slice(value);
+    attribute.resize(align(attribute.len()), 0);
+    attribute
+}
+
+/// The first globally scoped addresses of an interface.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Addresses {
+    pub ipv4: Option<Ipv4Addr>,
+    pub ipv6: Option<Ipv6Addr>,
+}
+
+/// Returns the addresses of the interface with the index `index`.
+pub fn addresses(index: u32) -> Result<Addresses, anyhow::Error> {
+    let mut socket = Socket::open(libc::NETLINK_ROUTE)?;
+    // struct ifaddrmsg of any family
+    let messages = socket.request(RTM_GETADDR, NLM_F_DUMP, &[0; 8])?;
+
+    let mut addresses = Addresses::default();
+
+    for message in messages {
+        let header = &message.payload;
+        if header.len() < 8 {
+            continue;
+        }
+
+        let family = i32::from(header[0]);
+        let scope = header[3];
+        let interface = u32::from_ne_bytes([header[4], header[5], header[6], header[7]]);
+        if interface != index || scope != RT_SCOPE_UNIVERSE {
+            continue;
+        }
+
+        let attributes = attributes(&header[8..]);
+        // IFA_LOCAL is the address of the interface itself on point to
+        // point links, where IFA_ADDRESS is the address of the peer.
+        let address = find(&attributes, IFA_LOCAL).or_else(|| find(&attributes, IFA_ADDRESS));
+
+        match (family, address) {
+            (libc::AF_INET, Some(x)) if addresses.ipv4.is_none() => {
+                if let Ok(x) = <[u8; 4]>::try_from(x) {
+                    addresses.ipv4 = Some(Ipv4Addr::from(x));
+                }
+            }
+            (libc::AF_INET6, Some(x)) if addresses.ipv6.is_none() => {
+                if let Ok(x) = <[u8; 16]>::try_from(x) {
+                    addresses.ipv6 = Some(Ipv6Addr::from(x));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(addresses)
+}
+
+/// Returns the index of the interface of the default route with the lowest
+/// metric, preferring IPv4 over IPv6.
+pub fn default_route() -> Result<Option<u32>, anyhow::Error> {
+    let mut socket = Socket::open(libc::NETLINK_ROUTE)?;
+
+    for family in [libc::AF_INET, libc::AF_INET6] {
+        // struct rtmsg, of which only the family is set
+        let mut request = [0; 12];
+        request[0] = family as u8;
+        let messages = socket.request(RTM_GETROUTE, NLM_F_DUMP, &request)?;
+
+        let mut best: Option<(u32, u32)> = None;
+
+        for message in messages {
+            let header = &message.payload;
+            if header.len() < 12 {
+                continue;
+            }
+
+            let destination_length = header[1];
+            let kind = header[7];
+            if destination_length != 0 || kind != RTN_UNICAST {
+                continue;
+            }
+
+            let attributes = attributes(&header[12..]);
+            // Tables above 255 are only given as an attribute.
+            let table = find_u32(&attributes, RTA_TABLE).unwrap_or(u32::from(header[4]));
+            let Some(interface) = find_u32(&attributes, RTA_OIF) else {
+                continue;
+            };
+            let priority = find_u32(&attributes, RTA_PRIORITY).unwrap_or(0);
+
+            if table == RT_TABLE_MAIN && best.is_none_or(|(x, _)| priority < x) {
+                best = Some((priority, interface));
+            }
+        }
+
+        if let Some((_, interface)) = best {
+            return Ok(Some(interface));
+        }
+    }
+
+    Ok(None)
+}
+
+/// A generic netlink socket bound to the family `name`, e.g. `nl80211`.
+struct Generic {
+    socket: Socket,
+    family: u16,
+}
+
+impl Generic {
+    fn open(name: &str) -> Result<Self, anyhow::Error> {
+        let mut socket = Socket::open(libc::NETLINK_GENERIC)?;
+
+        let mut value = name.as_bytes().to_vec();
+        value.push(0);
+        let messages = Self::send(
+            &mut socket,
+            GENL_ID_CTRL,
+            CTRL_CMD_GETFAMILY,
+            0,
+            &attribute(CTRL_ATTR_FAMILY_NAME, &value),
+        )
+        .map_err(|x| anyhow::anyhow!("Could not resolve netlink family {name}: {x}"))?;
+
+        let family = messages
+            .iter()
+            .find_map(|x| find(&attributes(x.payload.get(4..)?), CTRL_ATTR_FAMILY_ID))
+            .and_then(|x| Some(u16::from_ne_bytes(x.try_into().ok()?)))
+            .ok_or_else(|| anyhow::anyhow!("Unknown netlink family {name}"))?;
+
+        Ok(Self { socket, family })
+    }
+
+    fn send(
+        socket: &mut Socket,
+        family: u16,
+        command: u8,
+        flags: u16,
+        attributes: &[u8],
+    ) -> io::Result<Vec<Message>> {
+        // struct genlmsghdr: the command, the version and padding
+        let mut payload = vec![command, 1, 0, 0];
+        payload.extend_from_slice(attributes);
+        socket.request(family, flags, &payload)
+    }
+
+    /// Sends `command` and returns the attributes of every message of the
+    /// reply, to be split with `attributes`.
+    fn request(&mut self, command: u8, flags: u16, attributes: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+        let messages = Self::send(&mut self.socket, self.family, command, flags, attributes)?;
+
+        Ok(messages
+            .into_iter()
+            .filter(|x| x.kind == self.family && x.payload.len() >= 4)
+            .map(|x| x.payload[4..].to_vec())
+            .collect())
+    }
+}
+
+/// The connection of a wireless interface.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Wireless {
+    pub ssid: Option<String>,
+    /// The frequency of the channel in MHz
+    pub frequency: Option<u32>,
+    /// The signal strength of the access point in dBm
+    pub signal: Option<i32>,
+    /// The transmit bitrate in units of 100 kbit/s
+    pub bitrate: Option<u32>,
+}
+
+/// Returns the connection of the wireless interface with the index `index`.
+/// Everything is `None` while it is not connected.
+pub fn wireless(index: u32) -> Result<Wireless, anyhow::Error> {
+    let mut nl80211 = Generic::open("nl80211")?;
+    let interface = attribute(NL80211_ATTR_IFINDEX, &index.to_ne_bytes());
+
+    let mut wireless = Wireless::default();
+
+    for reply in nl80211.request(NL80211_CMD_GET_INTERFACE, 0, &interface)? {
+        read_interface(&mut wireless, &reply);
+    }
+
+    if wireless.ssid.is_none() {
+        return Ok(wireless);
+    }
+
+    // The only station of an interface in managed mode is its access point.
+    for reply in nl80211.request(NL80211_CMD_GET_STATION, NLM_F_DUMP, &interface)? {
+        read_station(&mut wireless, &reply);
+    }
+
+    Ok(wireless)
+}
+
+/// Reads the SSID and the frequency from the attributes of a reply to
+/// `NL80211_CMD_GET_INTERFACE`.
+fn read_interface(wireless: &mut Wireless, reply: &[u8]) {
+    let attributes = attributes(reply);
+    wireless.ssid =
+        find(&attributes, NL80211_ATTR_SSID).map(|x| String::from_utf8_lossy(x).into_owned());
+    wireless.frequency = find_u32(&attributes, NL80211_ATTR_WIPHY_FREQ);
+}
+
+/// Reads the signal and the bitrate from the nested station info of a
+/// reply to `NL80211_CMD_GET_STATION`.
+fn read_station(wireless: &mut Wireless, reply: &[u8]) {
+    let Some(info) = find(&attributes(reply), NL80211_ATTR_STA_INFO) else {
+        return;
+    };
+    let info = attributes(info);
+
+    wireless.signal = find(&info, NL80211_STA_INFO_SIGNAL)
+        .and_then(|x| x.first())
+        .map(|x| i32::from(*x as i8));
+    wireless.bitrate = find(&info, NL80211_STA_INFO_TX_BITRATE).and_then(|x| {
+        let rate = attributes(x);
+        find_u32(&rate, NL80211_RATE_INFO_BITRATE32).or_else(|| {
+            let x = find(&rate, NL80211_RATE_INFO_BITRATE)?;
+            Some(u32::from(u16::from_ne_bytes(x.try_into().ok()?)))
+        })
+    });
+}
+
+/// Returns the time of the latest handshake of any peer of the WireGuard
+/// interface with the index `index`, if there was one. Reading the device
+/// needs `CAP_NET_ADMIN`.
+pub fn wireguard_handshake(index: u32) -> Result<Option<SystemTime>, anyhow::Error> {
+    let mut wireguard = Generic::open("wireguard")?;
+    let replies = wireguard.request(
+        WG_CMD_GET_DEVICE,
+        NLM_F_DUMP,
+        &attribute(WGDEVICE_A_IFINDEX, &index.to_ne_bytes()),
+    )?;
+
+    Ok(latest_handshake(&replies))
+}
+
+/// Returns the latest handshake of the peers in the replies to
+/// `WG_CMD_GET_DEVICE`. Devices with many peers are split over several
+/// replies.
+fn latest_handshake(replies: &[Vec<u8>]) -> Option<SystemTime> {
+    let mut latest = None;
+
+    for reply in replies {
+        let Some(peers) = find(&attributes(reply), WGDEVICE_A_PEERS) else {
+            continue;
+        };
+
+        for (_, peer) in attributes(peers) {
+            // struct __kernel_timespec, zero if there was no handshake
+            let Some(time) = find(&attributes(peer), WGPEER_A_LAST_HANDSHAKE_TIME) else {
+                continue;
+            };
+            let Some(seconds) = time.get(..8).and_then(|x| x.try_into().ok()) else {
+                continue;
+            };
+
+            let seconds = i64::from_ne_bytes(seconds);
+            if seconds > 0 {
+                let time = UNIX_EPOCH + Duration::from_secs(seconds as u64);
+                latest = latest.max(Some(time));
+            }
+        }
+    }
+
+    latest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NLA_F_NESTED: u16 = 0x8000;
+
+    fn nested(kind: u16, attributes: &[Vec<u8>]) -> Vec<u8> {
+        attribute(kind | NLA_F_NESTED, &attributes.concat())
+    }
+
+    #[test]
+    fn pads_attributes_to_four_bytes() {
+        let data = [attribute(1, b"wlan0"), attribute(2, &7u32.to_ne_bytes())].concat();
+
+        assert_eq!(data.len(), 12 + 8);
+        assert_eq!(&data[..2], &9u16.to_ne_bytes());
+        assert_eq!(&data[9..12], &[0, 0, 0]);
+        assert_eq!(
+            attributes(&data),
+            [(1, &b"wlan0"[..]), (2, &7u32.to_ne_bytes()[..])]
+        );
+        assert_eq!(find_u32(&attributes(&data), 2), Some(7));
+        assert_eq!(find_u32(&attributes(&data), 1), None);
+    }
+
+    #[test]
+    fn reads_an_unpadded_last_attribute() {
+        let mut data = attribute(1, b"ab");
+        data.extend_from_slice(&attribute(2, b"c")[..5]);
+
+        assert_eq!(attributes(&data), [(1, &b"ab"[..]), (2, &b"c"[..])]);
+    }
+
+    #[test]
+    fn stops_at_malformed_attributes() {
+        let valid = attribute(1, b"abcd");
+        let header = |length: u16, kind: u16| [length.to_ne_bytes(), kind.to_ne_bytes()].concat();
+
+        // The length exceeds the buffer.
+        let mut data = valid.clone();
+        data.extend_from_slice(&header(12, 2));
+        data.extend_from_slice(&[1, 2]);
+        assert_eq!(attributes(&data), [(1, &b"abcd"[..])]);
+
+        // The length is shorter than the header.
+        let mut data = valid.clone();
+        data.extend_from_slice(&header(2, 2));
+        assert_eq!(attributes(&data), [(1, &b"abcd"[..])]);
+
+        assert!(attributes(&8u16.to_ne_bytes()).is_empty());
+    }
+
+    #[test]
+    fn masks_the_flags_of_nested_attributes() {
+        let data = nested(
+            NL80211_ATTR_STA_INFO,
+            &[attribute(1, &[1]), attribute(2, &[2])],
+        );
+        let outer = attributes(&data);
+
+        assert_eq!(outer.len(), 1);
+        assert_eq!(outer[0].0, NL80211_ATTR_STA_INFO);
+        assert_eq!(attributes(outer[0].1), [(1, &[1][..]), (2, &[2][..])]);
+    }
+
+    #[test]
+    fn reads_the_interface_and_the_station() {
+        let mut wireless = Wireless::default();
+
+        let interface = [
+            attribute(NL80211_ATTR_IFINDEX, &3u32.to_ne_bytes()),
+            attribute(NL80211_ATTR_SSID, b"home"),
+            attribute(NL80211_ATTR_WIPHY_FREQ, &5180u32.to_ne_bytes()),
+        ]
+        .concat();
+        read_interface(&mut wireless, &interface);
+
+        let station = nested(
+            NL80211_ATTR_STA_INFO,
+            &[
+                attribute(NL80211_STA_INFO_SIGNAL, &[-52i8 as u8]),
+                nested(
+                    NL80211_STA_INFO_TX_BITRATE,
+                    &[
+                        attribute(NL80211_RATE_INFO_BITRATE, &100u16.to_ne_bytes()),
+                        attribute(NL80211_RATE_INFO_BITRATE32, &8667u32.to_ne_bytes()),
+                    ],
+                ),
+            ],
+        );
+        read_station(&mut wireless, &station);
+
+        assert_eq!(
+            wireless,
+            Wireless {
+                ssid: Some("home".to_string()),
+                frequency: Some(5180),
+                signal: Some(-52),
+                bitrate: Some(8667),
+            }
+        );
+
+        // Legacy rates are only given as 16 bit values.
+        let station = nested(
+            NL80211_ATTR_STA_INFO,
+            &[nested(
+                NL80211_STA_INFO_TX_BITRATE,
+                &[attribute(NL80211_RATE_INFO_BITRATE, &540u16.to_ne_bytes())],
+            )],
+        );
+        read_station(&mut wireless, &station);
+        assert_eq!((wireless.signal, wireless.bitrate), (None, Some(540)));
+    }
+
+    #[test]
+    fn reads_the_latest_handshake() {
+        let peer = |seconds: i64| {
+            let timespec = [seconds.to_ne_bytes(), 0i64.to_ne_bytes()].concat();
+            nested(0, &[attribute(WGPEER_A_LAST_HANDSHAKE_TIME, &timespec)])
+        };
+        let device = |peers: &[Vec<u8>]| {
+            [
+                attribute(WGDEVICE_A_IFINDEX, &4u32.to_ne_bytes()),
+                nested(WGDEVICE_A_PEERS, peers),
+            ]
+            .concat()
+        };
+
+        let replies = [
+            device(&[peer(100), peer(0)]),
+            device(&[peer(300), peer(200)]),
+        ];
+        assert_eq!(
+            latest_handshake(&replies),
+            Some(UNIX_EPOCH + Duration::from_secs(300))
+        );
+
+        assert_eq!(latest_handshake(&[device(&[peer(0)])]), None);
+        assert_eq!(latest_handshake(&[]), None);
+    }
+}
@@ -0,0 +1,120 @@
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::mpsc;
+
+use super::{Click, Output};
+use crate::format::FormatString;
+use crate::property::ShowBars;
+
+#[derive(Serialize)]
+struct I3barHeader {
+    version: u32,
+    click_events: bool,
+}
+
+#[derive(Serialize)]
+struct I3barBlock<'a> {
+    name: &'a str,
+    instance: &'a str,
+    full_text: &'a str,
+    color: String,
+    background: String,
+}
+
+#[derive(Deserialize)]
+struct I3barClick {
+    name: Option<String>,
+    instance: Option<String>,
+    button: u8,
+}
+
+pub struct I3barOutput {
+    stdout: io::Stdout,
+}
+
+impl I3barOutput {
+    pub fn new() -> Result<Self, anyhow::Error> {
+        let stdout = io::stdout();
+        let header = serde_json::to_string(&I3barHeader {
+            version: 1,
+            click_events: true,
+        })?;
+
+        // The body of the protocol is an infinite array of status lines.
+        let mut lock = stdout.lock();
+        writeln!(lock, "{header}")?;
+        writeln!(lock, "[")?;
+        lock.flush()?;
+        drop(lock);
+
+        Ok(Self { stdout })
+    }
+}
+
+impl Output for I3barOutput {
+    fn write(&mut self, format: &FormatString, bar: &ShowBars) -> Result<(), anyhow::Error> {
+        let error = bar.error().map(|block| ("error", "error", block));
+        let blocks: Vec<I3barBlock> = error
+            .into_iter()
+            .chain(format.placeholders().filter_map(|name| {
+                let (kind, block) = bar.get(name)?;
+                (!block.full_text.is_empty()).then_some((kind, name, block))
+            }))
+            .map(|(kind, name, block)| I3barBlock {
+                name: kind,
+                instance: name,
+                full_text: block.full_text.trim(),
+                color: block.fgcolor.to_string(),
+                background: block.bgcolor.to_string(),
+            })
+            .collect();
+
+        let mut stdout = self.stdout.lock();
+        writeln!(stdout, "{},", serde_json::to_string(&blocks)?)?;
+        stdout.flush()?;
+
+        Ok(())
+    }
+
+    fn clicks(&mut self) -> mpsc::UnboundedReceiver<Click> {
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(tokio::io::stdin()).lines();
+
+            while let Ok(Some(line)) = lines.next_line().await {
+                // Click events are sent as an infinite array as well, so
+                // every event but the first is prefixed with a comma.
+                let line = line.trim().trim_start_matches(',');
+                if line.is_empty() || line == "[" {
+                    continue;
+                }
+
+                let click: I3barClick = match serde_json::from_str(line) {
+                    Ok(x) => x,
+                    Err(x) => {
+                        eprintln!("Invalid click event: {x}");
+                        continue;
+                    }
+                };
+
+                let Some(name) = click.instance.or(click.name) else {
+                    continue;
+                };
+
+                if tx
+                    .send(Click {
+                        name,
+                        button: click.button,
+                    })
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        rx
+    }
+}
@@ -0,0 +1,78 @@
+pub mod i3bar;
+pub mod stdout;
+pub mod x11;
+
+use serde::de;
+use serde::{Deserialize, Deserializer};
+use std::str::FromStr;
+use tokio::sync::mpsc;
+
+use crate::format::FormatString;
+use crate::markup::Markup;
+use crate::property::ShowBars;
+use i3bar::I3barOutput;
+use stdout::StdoutOutput;
+use x11::X11Output;
+
+/// A click on the module with the placeholder `name`.
+pub struct Click {
+    pub name: String,
+    pub button: u8,
+}
+
+pub trait Output {
+    fn write(&mut self, format: &FormatString, bar: &ShowBars) -> Result<(), anyhow::Error>;
+
+    /// Returns a channel of the click events reported by the output. Outputs
+    /// without click support return a closed channel.
+    fn clicks(&mut self) -> mpsc::UnboundedReceiver<Click> {
+        mpsc::unbounded_channel().1
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputKind {
+    /// Sets the `WM_NAME` of the X root window, which is read by dwm.
+    X11,
+    /// Prints one line per update, e.g. for swaybar, tmux or somebar.
+    Stdout,
+    /// Speaks the i3bar JSON protocol, used by i3bar and swaybar.
+    I3bar,
+}
+
+impl OutputKind {
+    pub fn create(self, markup: Markup) -> Result<Box<dyn Output>, anyhow::Error> {
+        Ok(match self {
+            OutputKind::X11 => Box::new(X11Output::new(markup)?),
+            OutputKind::Stdout => Box::new(StdoutOutput::new(markup)),
+            OutputKind::I3bar => Box::new(I3barOutput::new()?),
+        })
+    }
+}
+
+impl FromStr for OutputKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "x11" => Ok(OutputKind::X11),
+            "stdout" => Ok(OutputKind::Stdout),
+            "i3bar" => Ok(OutputKind::I3bar),
+            _ => Err(anyhow::anyhow!("{} is not a valid output", s)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for OutputKind {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s: String = Deserialize::deserialize(deserializer)?;
+
+        match OutputKind::from_str(&s) {
+            Ok(x) => Ok(x),
+            Err(x) => Err(de::Error::custom(x)),
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use std::io::{self, Write};
+
+use super::Output;
+use crate::format::FormatString;
+use crate::markup::Markup;
+use crate::property::ShowBars;
+
+pub struct StdoutOutput {
+    stdout: io::Stdout,
+    markup: Markup,
+}
+
+impl StdoutOutput {
+    pub fn new(markup: Markup) -> Self {
+        Self {
+            stdout: io::stdout(),
+            markup,
+        }
+    }
+}
+
+impl Output for StdoutOutput {
+    fn write(&mut self, format: &FormatString, bar: &ShowBars) -> Result<(), anyhow::Error> {
+        let bar = self.markup.render(format, bar);
+
+        let mut stdout = self.stdout.lock();
+        writeln!(stdout, "{bar}")?;
+        stdout.flush()?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,46 @@
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::*;
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt;
+
+use super::Output;
+use crate::format::FormatString;
+use crate::markup::Markup;
+use crate::property::ShowBars;
+
+pub struct X11Output {
+    conn: RustConnection,
+    root_window: Window,
+    markup: Markup,
+}
+
+impl X11Output {
+    pub fn new(markup: Markup) -> Result<Self, anyhow::Error> {
+        let (conn, screen_num) = RustConnection::connect(None)?;
+        let root_window = conn.setup().roots[screen_num].root;
+
+        Ok(Self {
+            conn,
+            root_window,
+            markup,
+        })
+    }
+}
+
+impl Output for X11Output {
+    fn write(&mut self, format: &FormatString, bar: &ShowBars) -> Result<(), anyhow::Error> {
+        let bar = self.markup.render(format, bar);
+
+        self.conn
+            .change_property8(
+                PropMode::REPLACE,
+                self.root_window,
+                AtomEnum::WM_NAME,
+                AtomEnum::STRING,
+                bar.as_bytes(),
+            )?
+            .check()?;
+
+        Ok(())
+    }
+}
@@ -1,58 +1,188 @@
 use async_trait::async_trait;
-use regex::Regex;
-use std::process::Command;
+use serde::de;
+use serde::{Deserialize, Deserializer};
+use std::str::FromStr;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
 
-use super::ShowBar;
-use crate::{config::RsbrConfig, icons};
+use super::{Block, ShowBar};
+use crate::alsa::{self, Direction, Mixer};
+use crate::config::{RsbrAudioConfig, RsbrConfig};
+use crate::hexcolor::ThemeColor;
+use crate::pulse::{self, DeviceKind};
 
-pub struct AudioProperty;
+const FIELDS: &[&str] = &["icon", "volume", "muted", "description"];
+const STATES: &[&str] = &["muted"];
+const ICONS: &[&str] = &["volume", "muted"];
 
-impl AudioProperty {
-    fn get_muted(&self) -> Result<bool, anyhow::Error> {
-        let output = Command::new("amixer").arg("get").arg("Master").output()?;
+/// Where the volume is read from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AudioBackend {
+    /// A mixer control of an ALSA sound card
+    Alsa,
+    /// A sink of a PulseAudio or pipewire-pulse server
+    Pulse,
+}
 
-        let output_str = String::from_utf8_lossy(&output.stdout);
-        let is_muted = output_str.contains("[off]");
+impl FromStr for AudioBackend {
+    type Err = anyhow::Error;
 
-        Ok(is_muted)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "alsa" => Ok(AudioBackend::Alsa),
+            "pulse" | "pulseaudio" | "pipewire" => Ok(AudioBackend::Pulse),
+            _ => Err(anyhow::anyhow!("{} is not a valid audio backend", s)),
+        }
     }
+}
 
-    fn get_volume(&self) -> Result<u32, anyhow::Error> {
-        let output = Command::new("amixer").arg("get").arg("Master").output()?;
+impl<'de> Deserialize<'de> for AudioBackend {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s: String = Deserialize::deserialize(deserializer)?;
 
-        let output_str = String::from_utf8_lossy(&output.stdout);
+        match AudioBackend::from_str(&s) {
+            Ok(x) => Ok(x),
+            Err(x) => Err(de::Error::custom(x)),
+        }
+    }
+}
 
-        let re = Regex::new(r"\d+%")?;
+struct AudioInfo {
+    volume: u32,
+    muted: bool,
+    description: String,
+}
 
-        if let Some(mat) = re.find(&output_str) {
-            let volume_str = &mat.as_str()[..mat.as_str().len() - 1];
+pub struct AudioProperty {
+    config: RsbrAudioConfig,
+    pulse: pulse::Client,
+}
 
-            if let Ok(volume) = volume_str.parse::<u32>() {
-                return Ok(volume);
-            }
+impl AudioProperty {
+    pub fn new(config: RsbrAudioConfig) -> Self {
+        Self {
+            config,
+            pulse: pulse::Client::default(),
         }
+    }
+
+    fn read_mixer(card: &str, control: &str) -> Result<AudioInfo, anyhow::Error> {
+        let mixer = Mixer::open(card, control, Direction::Playback)?;
 
-        Err(anyhow::anyhow!("Could not retrieve Master Volume!"))
+        Ok(AudioInfo {
+            volume: mixer.volume()?,
+            muted: mixer.muted()?,
+            description: control.to_string(),
+        })
     }
 
-    fn get_audio(&self) -> Result<(u32, bool), anyhow::Error> {
-        let volume_perc = self.get_volume()?;
-        let muted = self.get_muted()?;
+    async fn get_audio(&self) -> Result<AudioInfo, anyhow::Error> {
+        match self.config.backend {
+            AudioBackend::Alsa => {
+                let card = self.config.card.clone();
+                let control = self.config.control.clone();
+
+                tokio::task::spawn_blocking(move || Self::read_mixer(&card, &control)).await?
+            }
+            AudioBackend::Pulse => {
+                let sink = self
+                    .pulse
+                    .device_info(DeviceKind::Sink, self.config.sink.as_deref())
+                    .await?;
 
-        Ok((volume_perc, muted))
+                Ok(AudioInfo {
+                    volume: sink.volume,
+                    muted: sink.muted,
+                    description: sink.description,
+                })
+            }
+        }
     }
 }
 
 #[async_trait]
 impl ShowBar for AudioProperty {
-    async fn show_bar(&self, config: &RsbrConfig, template: &str) -> String {
-        let audio = match self.get_audio() {
-            Ok((volume_percent, is_muted)) => {
-                format!(
-                    "{} {}%",
-                    icons::get_volume_icon(volume_percent, is_muted).to_string(),
-                    volume_percent
-                )
+    fn kind(&self) -> &'static str {
+        "audio"
+    }
+
+    fn name(&self) -> &str {
+        self.config.name.as_deref().unwrap_or("audio")
+    }
+
+    fn interval(&self) -> u64 {
+        self.config.interval
+    }
+
+    fn signal(&self) -> Option<i32> {
+        self.config.signal
+    }
+
+    fn timeout(&self) -> u64 {
+        self.config.timeout
+    }
+
+    fn subscribe(&self) -> Option<(mpsc::UnboundedReceiver<()>, JoinHandle<()>)> {
+        match self.config.backend {
+            AudioBackend::Alsa => match alsa::subscribe(&self.config.card) {
+                Ok(x) => Some(x),
+                Err(x) => {
+                    eprintln!("Could not subscribe to mixer events: {x}");
+                    None
+                }
+            },
+            AudioBackend::Pulse => Some(pulse::Connection::subscribe()),
+        }
+    }
+
+    fn colors(&self) -> Vec<&ThemeColor> {
+        [&self.config.fgcolor, &self.config.bgcolor]
+            .into_iter()
+            .chain(self.config.states.colors())
+            .collect()
+    }
+
+    fn colors_mut(&mut self) -> Vec<&mut ThemeColor> {
+        [&mut self.config.fgcolor, &mut self.config.bgcolor]
+            .into_iter()
+            .chain(self.config.states.colors_mut())
+            .collect()
+    }
+
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        self.config.states.validate(STATES)?;
+        self.config.icons.validate(ICONS)?;
+        self.config.format.validate(FIELDS)
+    }
+
+    async fn show_bar(&self, config: &RsbrConfig) -> Block {
+        let mut state = None;
+        let audio = match self.get_audio().await {
+            Ok(x) => {
+                if x.muted {
+                    state = Some("muted");
+                }
+
+                self.config.format.render_fields(&[
+                    (
+                        "icon",
+                        self.config
+                            .icons
+                            .get(
+                                config.icons,
+                                "audio",
+                                if x.muted { "muted" } else { "volume" },
+                                f64::from(x.volume),
+                            )
+                            .to_string(),
+                    ),
+                    ("volume", x.volume.to_string()),
+                    ("muted", if x.muted { "muted" } else { "" }.to_string()),
+                    ("description", x.description),
+                ])
             }
             Err(x) => {
                 eprintln!("{x}");
@@ -60,14 +190,17 @@ impl ShowBar for AudioProperty {
             }
         };
 
-        template.replace(
-            "{audio}",
-            &format!(
-                "^c{}^^b{}^ {}",
-                &config.theme.get_color(&config.audio.fgcolor),
-                &config.theme.get_color(&config.audio.bgcolor),
-                audio.as_str()
-            ),
-        )
+        let (fgcolor, bgcolor) = self.config.states.block_colors(
+            &config.theme,
+            state,
+            &self.config.fgcolor,
+            &self.config.bgcolor,
+        );
+
+        Block {
+            full_text: audio,
+            fgcolor,
+            bgcolor,
+        }
     }
 }
@@ -0,0 +1,329 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+
+use super::network::InterfaceSelection;
+use super::{Block, ShowBar};
+use crate::config::{RsbrBandwidthConfig, RsbrConfig};
+use crate::hexcolor::ThemeColor;
+use crate::sysfs;
+
+const FIELDS: &[&str] = &["icon", "interface", "rx", "tx"];
+const ICONS: &[&str] = &["bandwidth"];
+const UNITS: &[&str] = &["B/s", "KiB/s", "MiB/s", "GiB/s", "TiB/s"];
+
+/// The byte counters of an interface at the last tick and the rates
+/// derived from them.
+struct Sample {
+    time: Instant,
+    rx_bytes: u64,
+    tx_bytes: u64,
+    rx_rate: f64,
+    tx_rate: f64,
+}
+
+pub struct BandwidthProperty {
+    config: RsbrBandwidthConfig,
+    samples: Arc<Mutex<HashMap<String, Sample>>>,
+}
+
+impl BandwidthProperty {
+    pub fn new(config: RsbrBandwidthConfig) -> Self {
+        Self {
+            config,
+            samples: Arc::default(),
+        }
+    }
+
+    /// Reads the received and transmitted bytes of every interface from
+    /// `/proc/net/dev`.
+    fn read_counters() -> Result<HashMap<String, (u64, u64)>, anyhow::Error> {
+        Ok(Self::parse_counters(&fs::read_to_string("/proc/net/dev")?))
+    }
+
+    /// Parses the received and transmitted bytes of every interface from
+    /// the contents of `/proc/net/dev`.
+    fn parse_counters(content: &str) -> HashMap<String, (u64, u64)> {
+        let mut counters = HashMap::new();
+
+        // The first two lines are headers.
+        for line in content.lines().skip(2) {
+            let Some((interface, fields)) = line.split_once(':') else {
+                continue;
+            };
+
+            // Eight receive columns are followed by eight transmit columns.
+            let fields: Vec<u64> = fields
+                .split_whitespace()
+                .map(|x| x.parse().unwrap_or(0))
+                .collect();
+            if fields.len() < 9 {
+                continue;
+            }
+
+            counters.insert(interface.trim().to_string(), (fields[0], fields[8]));
+        }
+
+        counters
+    }
+
+    /// Reads the counters of the selected interfaces and returns their
+    /// smoothed receive and transmit rates.
+    fn update(
+        selection: &InterfaceSelection,
+        smoothing: f64,
+        samples: &Mutex<HashMap<String, Sample>>,
+    ) -> Result<Vec<(String, f64, f64)>, anyhow::Error> {
+        let interfaces = match selection {
+            InterfaceSelection::Auto => vec![sysfs::primary_interface()?],
+            InterfaceSelection::List(x) => x.clone(),
+        };
+        let counters = Self::read_counters()?;
+        let mut samples = samples.lock().unwrap();
+        let rates = Self::sample(
+            &mut samples,
+            interfaces,
+            &counters,
+            Instant::now(),
+            smoothing,
+        );
+
+        if rates.is_empty() {
+            return Err(anyhow::anyhow!(
+                "No traffic statistics of the network interfaces found"
+            ));
+        }
+
+        Ok(rates)
+    }
+
+    /// Updates the samples of the interfaces with their counters at `now`
+    /// and returns their rates. The first tick of an interface and counters
+    /// that were reset yield a rate of zero. Samples of interfaces that are
+    /// no longer selected are dropped.
+    fn sample(
+        samples: &mut HashMap<String, Sample>,
+        interfaces: Vec<String>,
+        counters: &HashMap<String, (u64, u64)>,
+        now: Instant,
+        smoothing: f64,
+    ) -> Vec<(String, f64, f64)> {
+        samples.retain(|x, _| interfaces.contains(x));
+        let mut rates = vec![];
+
+        for interface in interfaces {
+            let Some(&(rx_bytes, tx_bytes)) = counters.get(&interface) else {
+                continue;
+            };
+
+            let (rx_rate, tx_rate) = match samples.get(&interface) {
+                Some(x) => {
+                    let seconds = now.duration_since(x.time).as_secs_f64();
+                    let rate = |bytes: u64, last: u64| match bytes.checked_sub(last) {
+                        Some(x) if seconds > 0.0 => x as f64 / seconds,
+                        _ => 0.0,
+                    };
+
+                    (
+                        smoothing * x.rx_rate + (1.0 - smoothing) * rate(rx_bytes, x.rx_bytes),
+                        smoothing * x.tx_rate + (1.0 - smoothing) * rate(tx_bytes, x.tx_bytes),
+                    )
+                }
+                None => (0.0, 0.0),
+            };
+
+            samples.insert(
+                interface.clone(),
+                Sample {
+                    time: now,
+                    rx_bytes,
+                    tx_bytes,
+                    rx_rate,
+                    tx_rate,
+                },
+            );
+            rates.push((interface, rx_rate, tx_rate));
+        }
+
+        rates
+    }
+
+    async fn get_rates(&self) -> Result<Vec<(String, f64, f64)>, anyhow::Error> {
+        let selection = self.config.interface.clone();
+        let smoothing = self.config.smoothing;
+        let samples = self.samples.clone();
+
+        tokio::task::spawn_blocking(move || Self::update(&selection, smoothing, &samples)).await?
+    }
+
+    /// Scales a rate in bytes per second to the largest binary unit in
+    /// which it is at least 1.
+    fn format_rate(rate: f64) -> String {
+        let mut rate = rate;
+        let mut unit = 0;
+
+        while rate >= 1024.0 && unit < UNITS.len() - 1 {
+            rate /= 1024.0;
+            unit += 1;
+        }
+
+        if unit == 0 {
+            format!("{rate:.0} {}", UNITS[unit])
+        } else {
+            format!("{rate:.1} {}", UNITS[unit])
+        }
+    }
+}
+
+#[async_trait]
+impl ShowBar for BandwidthProperty {
+    fn kind(&self) -> &'static str {
+        "bandwidth"
+    }
+
+    fn name(&self) -> &str {
+        self.config.name.as_deref().unwrap_or("bandwidth")
+    }
+
+    fn interval(&self) -> u64 {
+        self.config.interval
+    }
+
+    fn signal(&self) -> Option<i32> {
+        self.config.signal
+    }
+
+    fn timeout(&self) -> u64 {
+        self.config.timeout
+    }
+
+    fn colors(&self) -> Vec<&ThemeColor> {
+        vec![&self.config.fgcolor, &self.config.bgcolor]
+    }
+
+    fn colors_mut(&mut self) -> Vec<&mut ThemeColor> {
+        vec![&mut self.config.fgcolor, &mut self.config.bgcolor]
+    }
+
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        if !(0.0..1.0).contains(&self.config.smoothing) {
+            return Err(anyhow::anyhow!(
+                "The smoothing must be at least 0 and less than 1"
+            ));
+        }
+
+        self.config.icons.validate(ICONS)?;
+        self.config.format.validate(FIELDS)
+    }
+
+    async fn show_bar(&self, config: &RsbrConfig) -> Block {
+        let bandwidth = match self.get_rates().await {
+            Ok(x) => x
+                .into_iter()
+                .map(|(interface, rx, tx)| {
+                    self.config.format.render_fields(&[
+                        (
+                            "icon",
+                            self.config
+                                .icons
+                                .get(config.icons, "bandwidth", "bandwidth", 0.0)
+                                .to_string(),
+                        ),
+                        ("interface", interface),
+                        ("rx", Self::format_rate(rx)),
+                        ("tx", Self::format_rate(tx)),
+                    ])
+                })
+                .collect::<Vec<_>>()
+                .join(&self.config.separator),
+            Err(x) => {
+                eprintln!("{x}");
+                "No Network Found".to_string()
+            }
+        };
+
+        Block {
+            full_text: bandwidth,
+            fgcolor: config.theme.get_color(&self.config.fgcolor),
+            bgcolor: config.theme.get_color(&self.config.bgcolor),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_rates() {
+        assert_eq!(BandwidthProperty::format_rate(0.0), "0 B/s");
+        assert_eq!(BandwidthProperty::format_rate(1023.4), "1023 B/s");
+        assert_eq!(BandwidthProperty::format_rate(1024.0), "1.0 KiB/s");
+        assert_eq!(BandwidthProperty::format_rate(1536.0), "1.5 KiB/s");
+        assert_eq!(
+            BandwidthProperty::format_rate(5.0 * 1024.0 * 1024.0),
+            "5.0 MiB/s"
+        );
+    }
+
+    #[test]
+    fn parses_counters() {
+        let counters = BandwidthProperty::parse_counters(
+            "Inter-|   Receive                            |  Transmit\n \
+             face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed\n    \
+             lo: 1296      16    0    0    0     0          0         0     1296      16    0    0    0     0       0          0\n  \
+             eth0:18446744073709551615 2 0 0 0 0 0 0 42 1 0 0 0 0 0 0\n  \
+             wg0: 1 2 3\n",
+        );
+
+        assert_eq!(counters.len(), 2);
+        assert_eq!(counters["lo"], (1296, 1296));
+        assert_eq!(counters["eth0"], (u64::MAX, 42));
+    }
+
+    #[test]
+    fn samples_the_selected_interfaces() {
+        let start = Instant::now();
+        let sample = |rx_bytes, tx_bytes| Sample {
+            time: start,
+            rx_bytes,
+            tx_bytes,
+            rx_rate: 100.0,
+            tx_rate: 100.0,
+        };
+        let mut samples = HashMap::from([
+            ("eth0".to_string(), sample(1000, 5000)),
+            ("unplugged0".to_string(), sample(0, 0)),
+        ]);
+        let counters = HashMap::from([
+            ("eth0".to_string(), (3000, 1000)),
+            ("wlan0".to_string(), (7, 7)),
+        ]);
+        let interfaces = vec!["eth0".to_string(), "wlan0".to_string(), "wg0".to_string()];
+        let now = start + std::time::Duration::from_secs(2);
+
+        let rates = BandwidthProperty::sample(&mut samples, interfaces, &counters, now, 0.5);
+
+        // The transmit counter of eth0 was reset.
+        assert_eq!(
+            rates,
+            [
+                ("eth0".to_string(), 550.0, 50.0),
+                ("wlan0".to_string(), 0.0, 0.0)
+            ]
+        );
+        assert!(samples.contains_key("wlan0"));
+        assert!(!samples.contains_key("unplugged0"));
+        assert_eq!(samples["eth0"].rx_bytes, 3000);
+    }
+
+    #[test]
+    fn stops_at_the_largest_unit() {
+        assert_eq!(
+            BandwidthProperty::format_rate(2048.0 * 1024_f64.powi(4)),
+            "2048.0 TiB/s"
+        );
+    }
+}
@@ -1,13 +1,30 @@
 use async_trait::async_trait;
 
-use crate::{config::RsbrConfig, icons};
+use crate::config::{RsbrBatteryConfig, RsbrConfig};
+use crate::hexcolor::ThemeColor;
 
-use super::ShowBar;
+use super::{Block, ShowBar};
 
-pub struct BatteryProperty;
+const FIELDS: &[&str] = &["icon", "percent", "state", "time_remaining"];
+const STATES: &[&str] = &["critical", "warning", "good", "charging"];
+const ICONS: &[&str] = &["discharging", "charging"];
+
+pub struct BatteryProperty {
+    config: RsbrBatteryConfig,
+}
+
+struct BatteryInfo {
+    percent: f32,
+    state: battery::State,
+    time_remaining: Option<f32>,
+}
 
 impl BatteryProperty {
-    fn get_battery(&self) -> Result<(f32, bool), anyhow::Error> {
+    pub fn new(config: RsbrBatteryConfig) -> Self {
+        Self { config }
+    }
+
+    fn read_battery() -> Result<BatteryInfo, anyhow::Error> {
         let manager = battery::Manager::new()?;
 
         let batteries = match manager.batteries() {
@@ -15,7 +32,7 @@ impl BatteryProperty {
             Ok(bat) => bat.filter_map(Result::ok).collect(),
         };
 
-        if batteries.len() == 0 {
+        if batteries.is_empty() {
             return Err(anyhow::anyhow!("No Battery found!"));
         }
 
@@ -24,38 +41,144 @@ impl BatteryProperty {
             .map(|x| x.state_of_charge().get::<battery::units::ratio::percent>())
             .sum();
 
-        let battery_charging = batteries
+        let states: Vec<battery::State> = batteries.iter().map(|x| x.state()).collect();
+        let state = if states.contains(&battery::State::Charging) {
+            battery::State::Charging
+        } else if states.contains(&battery::State::Discharging) {
+            battery::State::Discharging
+        } else {
+            states[0]
+        };
+
+        let time_remaining = batteries
             .iter()
-            .map(|x| x.state())
-            .any(|x| x == battery::State::Charging);
+            .filter_map(|x| match state {
+                battery::State::Charging => x.time_to_full(),
+                _ => x.time_to_empty(),
+            })
+            .map(|x| x.get::<battery::units::time::second>())
+            .reduce(f32::max);
 
-        Ok((sum / batteries.len() as f32, battery_charging))
+        Ok(BatteryInfo {
+            percent: sum / batteries.len() as f32,
+            state,
+            time_remaining,
+        })
+    }
+
+    /// Charging takes precedence over the charge thresholds, which default
+    /// to 10% for critical, 25% for warning and 80% for good.
+    fn get_state(&self, battery: &BatteryInfo) -> Option<&'static str> {
+        let states = &self.config.states;
+        let percent = f64::from(battery.percent);
+
+        if battery.state == battery::State::Charging && states.contains("charging") {
+            Some("charging")
+        } else if states.is_below("critical", percent, 10.0) {
+            Some("critical")
+        } else if states.is_below("warning", percent, 25.0) {
+            Some("warning")
+        } else if states.is_above("good", percent, 80.0) {
+            Some("good")
+        } else {
+            None
+        }
+    }
+
+    async fn get_battery(&self) -> Result<BatteryInfo, anyhow::Error> {
+        tokio::task::spawn_blocking(Self::read_battery).await?
     }
 }
 
 #[async_trait]
 impl ShowBar for BatteryProperty {
-    async fn show_bar(&self, config: &RsbrConfig, template: &str) -> String {
-        let battery = match self.get_battery() {
-            Ok((battery_charge, battery_status)) => format!(
-                "{} {}",
-                icons::get_battery_icon(battery_charge, battery_status),
-                battery_charge.ceil()
-            ),
+    fn kind(&self) -> &'static str {
+        "battery"
+    }
+
+    fn name(&self) -> &str {
+        self.config.name.as_deref().unwrap_or("battery")
+    }
+
+    fn interval(&self) -> u64 {
+        self.config.interval
+    }
+
+    fn signal(&self) -> Option<i32> {
+        self.config.signal
+    }
+
+    fn timeout(&self) -> u64 {
+        self.config.timeout
+    }
+
+    fn colors(&self) -> Vec<&ThemeColor> {
+        [&self.config.fgcolor, &self.config.bgcolor]
+            .into_iter()
+            .chain(self.config.states.colors())
+            .collect()
+    }
+
+    fn colors_mut(&mut self) -> Vec<&mut ThemeColor> {
+        [&mut self.config.fgcolor, &mut self.config.bgcolor]
+            .into_iter()
+            .chain(self.config.states.colors_mut())
+            .collect()
+    }
+
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        self.config.states.validate(STATES)?;
+        self.config.icons.validate(ICONS)?;
+        self.config.format.validate(FIELDS)
+    }
+
+    async fn show_bar(&self, config: &RsbrConfig) -> Block {
+        let mut state = None;
+        let battery = match self.get_battery().await {
+            Ok(x) => {
+                state = self.get_state(&x);
+                let icon = match x.state {
+                    battery::State::Charging => "charging",
+                    _ => "discharging",
+                };
+                let time_remaining = match x.time_remaining {
+                    Some(x) => {
+                        let minutes = (x / 60.0) as u32;
+                        format!("{}:{:02}", minutes / 60, minutes % 60)
+                    }
+                    None => String::new(),
+                };
+
+                self.config.format.render_fields(&[
+                    (
+                        "icon",
+                        self.config
+                            .icons
+                            .get(config.icons, "battery", icon, f64::from(x.percent))
+                            .to_string(),
+                    ),
+                    ("percent", x.percent.ceil().to_string()),
+                    ("state", x.state.to_string()),
+                    ("time_remaining", time_remaining),
+                ])
+            }
             Err(x) => {
                 eprintln!("{x}");
-                "No Battery Found".to_string()
+                "No Battery Found ".to_string()
             }
         };
 
-        template.replace(
-            "{battery}",
-            &format!(
-                "^c{}^^b{}^ {}% ",
-                &config.theme.get_color(&config.battery.fgcolor),
-                &config.theme.get_color(&config.battery.bgcolor),
-                battery.as_str()
-            ),
-        )
+        let (fgcolor, bgcolor) = self.config.states.block_colors(
+            &config.theme,
+            state,
+            &self.config.fgcolor,
+            &self.config.bgcolor,
+        );
+
+        Block {
+            full_text: battery,
+            fgcolor,
+            bgcolor,
+        }
     }
 }
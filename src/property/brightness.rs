@@ -1,13 +1,23 @@
-use super::ShowBar;
-use crate::{icons, config::RsbrConfig};
+use super::{Block, ShowBar};
+use crate::config::{RsbrBrightnessConfig, RsbrConfig};
+use crate::hexcolor::ThemeColor;
 
 use async_trait::async_trait;
 use brightness::Brightness;
 use futures_util::stream::StreamExt;
 
-pub struct BrightnessProperty;
+const FIELDS: &[&str] = &["icon", "percent"];
+const ICONS: &[&str] = &["brightness"];
+
+pub struct BrightnessProperty {
+    config: RsbrBrightnessConfig,
+}
 
 impl BrightnessProperty {
+    pub fn new(config: RsbrBrightnessConfig) -> Self {
+        Self { config }
+    }
+
     async fn get_brightness(&self) -> Result<u32, anyhow::Error> {
         match brightness::brightness_devices().next().await {
             Some(x) => match x {
@@ -21,23 +31,61 @@ impl BrightnessProperty {
 
 #[async_trait]
 impl ShowBar for BrightnessProperty {
-    async fn show_bar(&self, config: &RsbrConfig, template: &str) -> String {
+    fn kind(&self) -> &'static str {
+        "brightness"
+    }
+
+    fn name(&self) -> &str {
+        self.config.name.as_deref().unwrap_or("brightness")
+    }
+
+    fn interval(&self) -> u64 {
+        self.config.interval
+    }
+
+    fn signal(&self) -> Option<i32> {
+        self.config.signal
+    }
+
+    fn timeout(&self) -> u64 {
+        self.config.timeout
+    }
+
+    fn colors(&self) -> Vec<&ThemeColor> {
+        vec![&self.config.fgcolor, &self.config.bgcolor]
+    }
+
+    fn colors_mut(&mut self) -> Vec<&mut ThemeColor> {
+        vec![&mut self.config.fgcolor, &mut self.config.bgcolor]
+    }
+
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        self.config.icons.validate(ICONS)?;
+        self.config.format.validate(FIELDS)
+    }
+
+    async fn show_bar(&self, config: &RsbrConfig) -> Block {
         let brightness = match self.get_brightness().await {
-            Ok(x) => format!("{} {}", icons::get_brightness_icon(x), x),
+            Ok(x) => self.config.format.render_fields(&[
+                (
+                    "icon",
+                    self.config
+                        .icons
+                        .get(config.icons, "brightness", "brightness", f64::from(x))
+                        .to_string(),
+                ),
+                ("percent", x.to_string()),
+            ]),
             Err(x) => {
                 eprintln!("{x}");
-                "No Brightness Device".to_string()
+                "No Brightness Device ".to_string()
             }
         };
 
-        template.replace(
-            "{brightness}",
-            &format!(
-                "^c{}^^b{}^ {}% ",
-                &config.theme.get_color(&config.brightness.fgcolor),
-                &config.theme.get_color(&config.brightness.bgcolor),
-                brightness.as_str()
-            ),
-        )
+        Block {
+            full_text: brightness,
+            fgcolor: config.theme.get_color(&self.config.fgcolor),
+            bgcolor: config.theme.get_color(&self.config.bgcolor),
+        }
     }
 }
@@ -0,0 +1,107 @@
+use async_trait::async_trait;
+use tokio::process::Command;
+
+use super::{Block, ShowBar};
+use crate::config::{RsbrConfig, RsbrCustomConfig};
+use crate::hexcolor::ThemeColor;
+
+const FIELDS: &[&str] = &["output"];
+
+pub struct CustomProperty {
+    config: RsbrCustomConfig,
+}
+
+impl CustomProperty {
+    pub fn new(config: RsbrCustomConfig) -> Self {
+        Self { config }
+    }
+
+    async fn get_output(&self) -> Result<String, anyhow::Error> {
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(&self.config.command)
+            .kill_on_drop(true)
+            .output()
+            .await?;
+
+        let output_str = String::from_utf8_lossy(&output.stdout);
+
+        Ok(output_str.lines().next().unwrap_or_default().to_string())
+    }
+}
+
+#[async_trait]
+impl ShowBar for CustomProperty {
+    fn kind(&self) -> &'static str {
+        "custom"
+    }
+
+    fn name(&self) -> &str {
+        self.config.name.as_deref().unwrap_or("custom")
+    }
+
+    fn interval(&self) -> u64 {
+        self.config.interval
+    }
+
+    fn signal(&self) -> Option<i32> {
+        self.config.signal
+    }
+
+    fn timeout(&self) -> u64 {
+        self.config.timeout
+    }
+
+    fn colors(&self) -> Vec<&ThemeColor> {
+        vec![&self.config.fgcolor, &self.config.bgcolor]
+    }
+
+    fn colors_mut(&mut self) -> Vec<&mut ThemeColor> {
+        vec![&mut self.config.fgcolor, &mut self.config.bgcolor]
+    }
+
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        if self.config.command.is_empty() {
+            return Err(anyhow::anyhow!("No command given"));
+        }
+
+        self.config.format.validate(FIELDS)
+    }
+
+    async fn show_bar(&self, config: &RsbrConfig) -> Block {
+        let custom = match self.get_output().await {
+            Ok(x) => self.config.format.render_fields(&[("output", x)]),
+            Err(x) => {
+                eprintln!("{x}");
+                "Command Failed".to_string()
+            }
+        };
+
+        Block {
+            full_text: custom,
+            fgcolor: config.theme.get_color(&self.config.fgcolor),
+            bgcolor: config.theme.get_color(&self.config.bgcolor),
+        }
+    }
+
+    fn click(&self, button: u8) {
+        let Some(on_click) = &self.config.on_click else {
+            return;
+        };
+
+        let child = Command::new("sh")
+            .arg("-c")
+            .arg(on_click)
+            .env("BLOCK_BUTTON", button.to_string())
+            .spawn();
+
+        // The command may run for long, e.g. open a menu, so it is waited
+        // for in the background.
+        match child {
+            Ok(mut x) => {
+                tokio::spawn(async move { x.wait().await });
+            }
+            Err(x) => eprintln!("{x}"),
+        }
+    }
+}
@@ -1,25 +1,77 @@
 use async_trait::async_trait;
-use chrono::Local;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{Local, Utc};
 
-use crate::config::RsbrConfig;
+use crate::config::{RsbrConfig, RsbrDatetimeConfig};
+use crate::format::SnippetError;
+use crate::hexcolor::ThemeColor;
 
-use super::ShowBar;
+use super::{Block, ShowBar};
 
-pub struct DatetimeProperty;
+pub struct DatetimeProperty {
+    config: RsbrDatetimeConfig,
+}
+
+impl DatetimeProperty {
+    pub fn new(config: RsbrDatetimeConfig) -> Self {
+        Self { config }
+    }
+}
 
 #[async_trait]
 impl ShowBar for DatetimeProperty {
-    async fn show_bar(&self, config: &RsbrConfig, template: &str) -> String {
-        let datetime = Local::now().format(&config.datetime.format).to_string();
-
-        template.replace(
-            "{datetime}",
-            &format!(
-                "^c{}^^b{}^ {} ",
-                &config.theme.get_color(&config.datetime.fgcolor),
-                &config.theme.get_color(&config.datetime.bgcolor),
-                datetime,
-            ),
-        )
+    fn kind(&self) -> &'static str {
+        "datetime"
+    }
+
+    fn name(&self) -> &str {
+        self.config.name.as_deref().unwrap_or("datetime")
+    }
+
+    fn interval(&self) -> u64 {
+        self.config.interval
+    }
+
+    fn signal(&self) -> Option<i32> {
+        self.config.signal
+    }
+
+    fn timeout(&self) -> u64 {
+        self.config.timeout
+    }
+
+    fn colors(&self) -> Vec<&ThemeColor> {
+        vec![&self.config.fgcolor, &self.config.bgcolor]
+    }
+
+    fn colors_mut(&mut self) -> Vec<&mut ThemeColor> {
+        vec![&mut self.config.fgcolor, &mut self.config.bgcolor]
+    }
+
+    /// An invalid strftime format would make chrono panic while rendering.
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        if StrftimeItems::new(&self.config.format).any(|x| matches!(x, Item::Error)) {
+            return Err(SnippetError {
+                snippet: self.config.format.clone(),
+                message: format!("Invalid strftime format \"{}\"", self.config.format),
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+
+    async fn show_bar(&self, config: &RsbrConfig) -> Block {
+        let datetime = if self.config.utc {
+            Utc::now().format(&self.config.format).to_string()
+        } else {
+            Local::now().format(&self.config.format).to_string()
+        };
+
+        Block {
+            full_text: format!("{datetime} "),
+            fgcolor: config.theme.get_color(&self.config.fgcolor),
+            bgcolor: config.theme.get_color(&self.config.bgcolor),
+        }
     }
 }
@@ -0,0 +1,176 @@
+use async_trait::async_trait;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use super::audio::AudioBackend;
+use super::{Block, ShowBar};
+use crate::alsa::{self, Direction, Mixer};
+use crate::config::{RsbrConfig, RsbrMicrophoneConfig};
+use crate::hexcolor::ThemeColor;
+use crate::pulse::{self, DeviceKind};
+
+const FIELDS: &[&str] = &["icon", "volume", "muted", "description"];
+const STATES: &[&str] = &["muted", "live"];
+const ICONS: &[&str] = &["volume", "muted"];
+
+struct MicrophoneInfo {
+    volume: u32,
+    muted: bool,
+    description: String,
+    /// Whether an application is recording
+    recording: bool,
+}
+
+pub struct MicrophoneProperty {
+    config: RsbrMicrophoneConfig,
+    pulse: pulse::Client,
+}
+
+impl MicrophoneProperty {
+    pub fn new(config: RsbrMicrophoneConfig) -> Self {
+        Self {
+            config,
+            pulse: pulse::Client::default(),
+        }
+    }
+
+    fn read_mixer(card: &str, control: &str) -> Result<MicrophoneInfo, anyhow::Error> {
+        let mixer = Mixer::open(card, control, Direction::Capture)?;
+
+        Ok(MicrophoneInfo {
+            volume: mixer.volume()?,
+            muted: mixer.muted()?,
+            description: control.to_string(),
+            recording: alsa::capturing(card)?,
+        })
+    }
+
+    async fn get_microphone(&self) -> Result<MicrophoneInfo, anyhow::Error> {
+        match self.config.backend {
+            AudioBackend::Alsa => {
+                let card = self.config.card.clone();
+                let control = self.config.control.clone();
+
+                tokio::task::spawn_blocking(move || Self::read_mixer(&card, &control)).await?
+            }
+            AudioBackend::Pulse => {
+                let source = self
+                    .pulse
+                    .device_info(DeviceKind::Source, self.config.source.as_deref())
+                    .await?;
+
+                Ok(MicrophoneInfo {
+                    volume: source.volume,
+                    muted: source.muted,
+                    description: source.description,
+                    recording: source.running,
+                })
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ShowBar for MicrophoneProperty {
+    fn kind(&self) -> &'static str {
+        "microphone"
+    }
+
+    fn name(&self) -> &str {
+        self.config.name.as_deref().unwrap_or("microphone")
+    }
+
+    fn interval(&self) -> u64 {
+        self.config.interval
+    }
+
+    fn signal(&self) -> Option<i32> {
+        self.config.signal
+    }
+
+    fn timeout(&self) -> u64 {
+        self.config.timeout
+    }
+
+    fn subscribe(&self) -> Option<(mpsc::UnboundedReceiver<()>, JoinHandle<()>)> {
+        match self.config.backend {
+            AudioBackend::Alsa => match alsa::subscribe(&self.config.card) {
+                Ok(x) => Some(x),
+                Err(x) => {
+                    eprintln!("Could not subscribe to mixer events: {x}");
+                    None
+                }
+            },
+            AudioBackend::Pulse => Some(pulse::Connection::subscribe()),
+        }
+    }
+
+    fn colors(&self) -> Vec<&ThemeColor> {
+        [&self.config.fgcolor, &self.config.bgcolor]
+            .into_iter()
+            .chain(self.config.states.colors())
+            .collect()
+    }
+
+    fn colors_mut(&mut self) -> Vec<&mut ThemeColor> {
+        [&mut self.config.fgcolor, &mut self.config.bgcolor]
+            .into_iter()
+            .chain(self.config.states.colors_mut())
+            .collect()
+    }
+
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        self.config.states.validate(STATES)?;
+        self.config.icons.validate(ICONS)?;
+        self.config.format.validate(FIELDS)
+    }
+
+    async fn show_bar(&self, config: &RsbrConfig) -> Block {
+        let mut state = None;
+        let microphone = match self.get_microphone().await {
+            Ok(x) if self.config.hide_idle && !x.recording => String::new(),
+            Ok(x) => {
+                if x.muted {
+                    state = Some("muted");
+                } else if x.recording {
+                    state = Some("live");
+                }
+
+                self.config.format.render_fields(&[
+                    (
+                        "icon",
+                        self.config
+                            .icons
+                            .get(
+                                config.icons,
+                                "microphone",
+                                if x.muted { "muted" } else { "volume" },
+                                f64::from(x.volume),
+                            )
+                            .to_string(),
+                    ),
+                    ("volume", x.volume.to_string()),
+                    ("muted", if x.muted { "muted" } else { "" }.to_string()),
+                    ("description", x.description),
+                ])
+            }
+            Err(x) => {
+                eprintln!("{x}");
+                "No Microphone Found".to_string()
+            }
+        };
+
+        let (fgcolor, bgcolor) = self.config.states.block_colors(
+            &config.theme,
+            state,
+            &self.config.fgcolor,
+            &self.config.bgcolor,
+        );
+
+        Block {
+            full_text: microphone,
+            fgcolor,
+            bgcolor,
+        }
+    }
+}
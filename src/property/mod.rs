@@ -1,33 +1,575 @@
+pub mod audio;
+pub mod bandwidth;
 pub mod battery;
-pub mod datetime;
 pub mod brightness;
-pub mod audio;
+pub mod custom;
+pub mod datetime;
+pub mod microphone;
 pub mod network;
+pub mod vpn;
 
 use async_trait::async_trait;
+use std::fmt;
+use std::sync::Arc;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{timeout, Duration, Instant};
 
 use crate::config::RsbrConfig;
+use crate::format::{self, SnippetError};
+use crate::hexcolor::{HexColor, Theme, ThemeColor};
+use audio::AudioProperty;
+use bandwidth::BandwidthProperty;
+use battery::BatteryProperty;
+use brightness::BrightnessProperty;
+use custom::CustomProperty;
+use datetime::DatetimeProperty;
+use microphone::MicrophoneProperty;
+use network::NetworkProperty;
+use vpn::VpnProperty;
+
+/// The rendered text of a module together with its colors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub full_text: String,
+    pub fgcolor: HexColor,
+    pub bgcolor: HexColor,
+}
+
+/// The module an error of `ShowBars::errors` belongs to, shown as `[name]`.
+#[derive(Debug)]
+pub struct ModuleContext {
+    pub kind: &'static str,
+    pub name: String,
+}
+
+impl fmt::Display for ModuleContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}]", self.name)
+    }
+}
 
 #[async_trait]
-pub trait ShowBar {
-    async fn show_bar(&self, config: &RsbrConfig, template: &str) -> String;
+pub trait ShowBar: Send + Sync {
+    /// Type of the module, e.g. `datetime`.
+    fn kind(&self) -> &'static str;
+
+    /// Placeholder name of the module in the `format` string.
+    fn name(&self) -> &str;
+
+    /// Refresh interval in seconds. `0` only renders the module once.
+    fn interval(&self) -> u64;
+
+    /// Offset `N` of the real-time signal `SIGRTMIN+N` that forces a refresh.
+    fn signal(&self) -> Option<i32>;
+
+    /// Time in seconds after which a pending render is abandoned.
+    fn timeout(&self) -> u64;
+
+    /// Returns a channel that yields whenever the module should be rendered
+    /// again before its interval has elapsed, e.g. on a mixer event, and the
+    /// task that feeds it.
+    fn subscribe(&self) -> Option<(mpsc::UnboundedReceiver<()>, JoinHandle<()>)> {
+        None
+    }
+
+    /// The theme colors the module is configured with.
+    fn colors(&self) -> Vec<&ThemeColor>;
+
+    /// The same colors as `colors`, to be replaced by what they resolve to.
+    fn colors_mut(&mut self) -> Vec<&mut ThemeColor>;
+
+    /// Checks the module's own configuration, e.g. the fields of its format.
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+
+    async fn show_bar(&self, config: &RsbrConfig) -> Block;
+
+    /// Called when the module is clicked with the given mouse button. Only
+    /// outputs that report click events dispatch to it. It must not block
+    /// the bar.
+    fn click(&self, _button: u8) {}
+}
+
+struct Segment {
+    property: Arc<dyn ShowBar>,
+    block: Option<Block>,
+    last_update: Option<Instant>,
+    /// Whether a render task of the module is running.
+    rendering: bool,
 }
 
-pub struct ShowBars(Vec<Box<dyn ShowBar>>);
+impl Segment {
+    fn is_due(&self, now: Instant) -> bool {
+        !self.rendering
+            && (self.last_update.is_none() || self.next_update().is_some_and(|x| x <= now))
+    }
+
+    /// A module that is being rendered is not due until its block arrives.
+    fn next_update(&self) -> Option<Instant> {
+        if self.rendering {
+            return None;
+        }
+
+        match self.last_update {
+            None => Some(Instant::now()),
+            Some(last_update) => match self.property.interval() {
+                0 => None,
+                interval => Some(last_update + Duration::from_secs(interval)),
+            },
+        }
+    }
+}
+
+/// Creates one property for every module instance in the config.
+pub fn from_config(config: &RsbrConfig) -> Vec<Box<dyn ShowBar>> {
+    let mut properties: Vec<Box<dyn ShowBar>> = vec![];
+
+    for x in &config.battery {
+        properties.push(Box::new(BatteryProperty::new(x.clone())));
+    }
+    for x in &config.brightness {
+        properties.push(Box::new(BrightnessProperty::new(x.clone())));
+    }
+    for x in &config.datetime {
+        properties.push(Box::new(DatetimeProperty::new(x.clone())));
+    }
+    for x in &config.audio {
+        properties.push(Box::new(AudioProperty::new(x.clone())));
+    }
+    for x in &config.microphone {
+        properties.push(Box::new(MicrophoneProperty::new(x.clone())));
+    }
+    for x in &config.network {
+        properties.push(Box::new(NetworkProperty::new(x.clone())));
+    }
+    for x in &config.bandwidth {
+        properties.push(Box::new(BandwidthProperty::new(x.clone())));
+    }
+    for x in &config.vpn {
+        properties.push(Box::new(VpnProperty::new(x.clone())));
+    }
+    for x in &config.custom {
+        properties.push(Box::new(CustomProperty::new(x.clone())));
+    }
+
+    properties
+}
+
+/// Creates one property for every module instance that is referenced in
+/// the `format` string. The others would be rendered without being shown.
+pub fn placed(config: &RsbrConfig) -> Vec<Box<dyn ShowBar>> {
+    let names: Vec<&str> = config.format.placeholders().collect();
+    let mut properties = from_config(config);
+    properties.retain(|x| names.contains(&x.name()));
+
+    properties
+}
+
+/// A rendered block, or `None` if the module timed out, and the index of
+/// its module.
+type Rendered = (usize, Option<Block>);
+
+pub struct ShowBars {
+    segments: Vec<Segment>,
+    error: Option<Block>,
+    listeners: Vec<JoinHandle<()>>,
+    rendered_tx: mpsc::UnboundedSender<Rendered>,
+    rendered_rx: mpsc::UnboundedReceiver<Rendered>,
+}
 
 impl ShowBars {
     pub fn new(bar: Vec<Box<dyn ShowBar>>) -> Self {
-        Self(bar)
+        let (rendered_tx, rendered_rx) = mpsc::unbounded_channel();
+
+        Self {
+            segments: bar
+                .into_iter()
+                .map(|property| Segment {
+                    property: property.into(),
+                    block: None,
+                    last_update: None,
+                    rendering: false,
+                })
+                .collect(),
+            error: None,
+            listeners: vec![],
+            rendered_tx,
+            rendered_rx,
+        }
+    }
+
+    /// Shows `message` in front of the bar, e.g. a config that failed to load.
+    pub fn set_error(&mut self, config: &RsbrConfig, message: &str) {
+        self.error = Some(Block {
+            full_text: format!("{message} "),
+            fgcolor: config.theme.named("background"),
+            bgcolor: config.theme.named("red"),
+        });
+    }
+
+    pub fn error(&self) -> Option<&Block> {
+        self.error.as_ref()
+    }
+
+    /// Spawns a render task for every module whose interval has elapsed.
+    /// The tasks run concurrently and independently of the bar, so a slow
+    /// module only delays its own block, which `receive` picks up.
+    pub fn render(&mut self, config: &Arc<RsbrConfig>) {
+        let now = Instant::now();
+
+        for (index, segment) in self.segments.iter_mut().enumerate() {
+            if !segment.is_due(now) {
+                continue;
+            }
+
+            let property = segment.property.clone();
+            let config = config.clone();
+            let tx = self.rendered_tx.clone();
+            tokio::spawn(async move {
+                let duration = Duration::from_secs(property.timeout());
+                let block = timeout(duration, property.show_bar(&config)).await.ok();
+                let _ = tx.send((index, block));
+            });
+
+            segment.rendering = true;
+            segment.last_update = Some(now);
+        }
     }
 
-    pub async fn process(&self, config: RsbrConfig) -> String {
-        let attributes = &self.0;
-        let mut template = config.format.clone();
+    /// Waits for the next rendered block and takes every other block that
+    /// has arrived by then. A module that exceeded its timeout keeps its
+    /// previous block. Returns whether any block changed.
+    pub async fn receive(&mut self, config: &RsbrConfig) -> bool {
+        let Some(first) = self.rendered_rx.recv().await else {
+            return false;
+        };
 
-        for attribute in attributes {
-            template = attribute.as_ref().show_bar(&config, &template).await.to_string();
+        let mut changed = self.store(config, first);
+        while let Ok(x) = self.rendered_rx.try_recv() {
+            changed |= self.store(config, x);
         }
 
-        template
+        changed
+    }
+
+    /// Renders every module that is due and waits for all of their blocks,
+    /// e.g. to print the bar once.
+    pub async fn render_all(&mut self, config: &Arc<RsbrConfig>) {
+        self.render(config);
+
+        while self.segments.iter().any(|x| x.rendering) {
+            self.receive(config).await;
+        }
+    }
+
+    fn store(&mut self, config: &RsbrConfig, (index, block): Rendered) -> bool {
+        let segment = &mut self.segments[index];
+        segment.rendering = false;
+
+        let block = match block {
+            Some(x) => x,
+            None => {
+                eprintln!("{} timed out!", segment.property.name());
+                match &segment.block {
+                    Some(x) => x.clone(),
+                    None => Block {
+                        full_text: format!("{} timed out", segment.property.name()),
+                        fgcolor: config.theme.named("foreground"),
+                        bgcolor: config.theme.named("background"),
+                    },
+                }
+            }
+        };
+
+        let changed = segment.block.as_ref() != Some(&block);
+        segment.block = Some(block);
+        changed
+    }
+
+    /// Returns the type and the last block of the module called `name`.
+    pub fn get(&self, name: &str) -> Option<(&str, &Block)> {
+        self.segments
+            .iter()
+            .find(|segment| segment.property.name() == name)
+            .and_then(|segment| Some((segment.property.kind(), segment.block.as_ref()?)))
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.segments
+            .iter()
+            .map(|segment| segment.property.name())
+            .collect()
+    }
+
+    /// Validates the `format` string and the configuration of every module,
+    /// failing on the first error.
+    pub fn validate(&self, config: &RsbrConfig) -> Result<(), anyhow::Error> {
+        match self.errors(config).into_iter().next() {
+            Some(x) => Err(x),
+            None => Ok(()),
+        }
+    }
+
+    /// Replaces the color expressions of the modules with the hex colors they
+    /// resolve to, so that rendering does not evaluate them again. Colors
+    /// that do not resolve are left to `validate`. Modules that are already
+    /// being rendered are skipped.
+    pub fn resolve_colors(&mut self, theme: &Theme) {
+        for segment in self.segments.iter_mut() {
+            let Some(property) = Arc::get_mut(&mut segment.property) else {
+                continue;
+            };
+
+            for color in property.colors_mut() {
+                if let Ok(x) = theme.resolve(color) {
+                    *color = ThemeColor::Hex(x);
+                }
+            }
+        }
+    }
+
+    /// Collects every error in the `format` string and the configuration of
+    /// the modules.
+    pub fn errors(&self, config: &RsbrConfig) -> Vec<anyhow::Error> {
+        let mut errors = vec![];
+
+        if let Err(x) = config.format.validate(&self.names()) {
+            errors.push(x);
+        }
+
+        let names = self.names();
+        for (index, name) in names.iter().enumerate() {
+            if names[..index].contains(name) && !names[index + 1..].contains(name) {
+                errors.push(anyhow::anyhow!("Module name {name} is used more than once"));
+            }
+        }
+
+        for segment in self.segments.iter() {
+            let kind = segment.property.kind();
+            let name = segment.property.name();
+            let colors = segment.property.colors();
+            let colors = colors
+                .into_iter()
+                .map(|x| config.theme.resolve(x).map(|_| ()));
+
+            let timeout = match segment.property.timeout() {
+                0 => Err(SnippetError {
+                    snippet: "timeout".to_string(),
+                    message: "The timeout must be at least 1 second".to_string(),
+                }
+                .into()),
+                _ => Ok(()),
+            };
+
+            // A name that is no placeholder could never be placed in `format`.
+            let placeholder = if format::is_placeholder(name) {
+                Ok(())
+            } else {
+                Err(SnippetError {
+                    snippet: name.to_string(),
+                    message: format!(
+                        "The name \"{name}\" cannot be a placeholder, \
+                         use letters, digits, _ and - only"
+                    ),
+                }
+                .into())
+            };
+
+            let results = [placeholder, timeout]
+                .into_iter()
+                .chain(colors)
+                .chain([segment.property.validate()]);
+
+            for x in results {
+                if let Err(x) = x {
+                    errors.push(x.context(ModuleContext {
+                        kind,
+                        name: name.to_string(),
+                    }));
+                }
+            }
+        }
+
+        errors
+    }
+
+    /// Marks the module at `index` as due, so the next call to `render`
+    /// renders it again regardless of its interval. A module that is being
+    /// rendered is rendered again once its block arrives.
+    pub fn refresh(&mut self, index: usize) {
+        if let Some(segment) = self.segments.get_mut(index) {
+            segment.last_update = None;
+        }
+    }
+
+    /// Dispatches a click to the module called `name` and renders it again.
+    pub fn click(&mut self, name: &str, button: u8) {
+        if let Some(index) = self.segments.iter().position(|x| x.property.name() == name) {
+            self.segments[index].property.click(button);
+            self.refresh(index);
+        }
+    }
+
+    /// Spawns a listener for every module with a configured signal or
+    /// events of its own. The returned channel yields the index of the
+    /// module that was signaled. The listeners run until the bar is dropped,
+    /// e.g. replaced by a reloaded config.
+    pub fn listen(&mut self) -> mpsc::UnboundedReceiver<usize> {
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        for (index, segment) in self.segments.iter().enumerate() {
+            if let Some((mut events, task)) = segment.property.subscribe() {
+                self.listeners.push(task);
+                let tx = tx.clone();
+                self.listeners.push(tokio::spawn(async move {
+                    while events.recv().await.is_some() {
+                        if tx.send(index).is_err() {
+                            break;
+                        }
+                    }
+                }));
+            }
+
+            let Some(offset) = segment.property.signal() else {
+                continue;
+            };
+
+            let signum = libc::SIGRTMIN() + offset;
+            if offset < 0 || signum > libc::SIGRTMAX() {
+                eprintln!(
+                    "Signal SIGRTMIN+{offset} of {} is out of range!",
+                    segment.property.name()
+                );
+                continue;
+            }
+
+            let mut stream = match signal(SignalKind::from_raw(signum)) {
+                Ok(x) => x,
+                Err(x) => {
+                    eprintln!("Could not listen for SIGRTMIN+{offset}: {x}");
+                    continue;
+                }
+            };
+
+            let tx = tx.clone();
+            self.listeners.push(tokio::spawn(async move {
+                while stream.recv().await.is_some() {
+                    if tx.send(index).is_err() {
+                        break;
+                    }
+                }
+            }));
+        }
+
+        rx
+    }
+
+    /// Returns the point in time at which the next module is due.
+    pub fn next_update(&self) -> Option<Instant> {
+        self.segments
+            .iter()
+            .filter_map(|segment| segment.next_update())
+            .min()
+    }
+}
+
+/// The listeners keep mixer devices and server connections open, so they are
+/// stopped with the bar instead of when their next event finds the channel
+/// closed.
+impl Drop for ShowBars {
+    fn drop(&mut self) {
+        for listener in &self.listeners {
+            listener.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn resolves_colors_once() {
+        let config = RsbrConfig::from_str(
+            "format = \"{datetime}\"\n\
+             [datetime]\nfgcolor = \"lighten(black, 50%)\"\nbgcolor = \"nope\"\n",
+        )
+        .unwrap();
+        let mut attributes = ShowBars::new(from_config(&config));
+        attributes.resolve_colors(&config.theme);
+
+        let segment = attributes
+            .segments
+            .iter()
+            .find(|x| x.property.kind() == "datetime")
+            .unwrap();
+        let colors = segment.property.colors();
+        assert!(matches!(colors[0], ThemeColor::Hex(x) if x.0 == "#808080"));
+        assert!(matches!(colors[1], ThemeColor::Named(x) if x == "nope"));
+    }
+
+    #[tokio::test]
+    async fn stops_the_listeners_when_dropped() {
+        let config =
+            RsbrConfig::from_str("format = \"{datetime}\"\n[datetime]\nsignal = 1\n").unwrap();
+        let mut attributes = ShowBars::new(from_config(&config));
+        let mut signals = attributes.listen();
+        drop(attributes);
+
+        let signal = timeout(Duration::from_secs(1), signals.recv()).await;
+        assert!(matches!(signal, Ok(None)));
+    }
+
+    #[tokio::test]
+    async fn renders_modules_independently() {
+        let config = RsbrConfig::from_str(
+            "format = \"{slow}{datetime}\"\n\
+             [[custom]]\nname = \"slow\"\ncommand = \"sleep 3\"\ntimeout = 5\n\
+             [datetime]\n",
+        )
+        .unwrap();
+        let config = Arc::new(config);
+        let mut attributes = ShowBars::new(placed(&config));
+        attributes.render(&config);
+
+        let changed = timeout(Duration::from_secs(1), attributes.receive(&config)).await;
+        assert!(matches!(changed, Ok(true)));
+        assert!(attributes.get("datetime").is_some());
+        assert!(attributes.get("slow").is_none());
+        assert!(attributes.next_update().is_some());
+    }
+
+    #[test]
+    fn rejects_a_zero_timeout() {
+        let config =
+            RsbrConfig::from_str("format = \"{datetime}\"\n[datetime]\ntimeout = 0\n").unwrap();
+        let errors = ShowBars::new(placed(&config)).errors(&config);
+
+        assert_eq!(errors.len(), 1);
+        let error = errors[0].downcast_ref::<SnippetError>().unwrap();
+        assert_eq!(error.snippet, "timeout");
+    }
+
+    #[test]
+    fn rejects_names_that_are_no_placeholders() {
+        let config = RsbrConfig::from_str(
+            "format = \"{datetime}\"\n\
+             [[custom]]\nname = \"my clock\"\ncommand = \"date\"\n\
+             [[custom]]\nname = \"{x}\"\ncommand = \"date\"\n\
+             [datetime]\n",
+        )
+        .unwrap();
+        let errors = ShowBars::new(from_config(&config)).errors(&config);
+        let snippets: Vec<&str> = errors
+            .iter()
+            .filter_map(|x| x.downcast_ref::<SnippetError>())
+            .map(|x| x.snippet.as_str())
+            .collect();
+
+        assert_eq!(snippets, ["my clock", "{x}"]);
     }
 }
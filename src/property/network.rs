@@ -1,58 +1,400 @@
 use async_trait::async_trait;
-use std::process::Command;
+use serde::de;
+use serde::{Deserialize, Deserializer};
+use std::path::Path;
 
-use super::ShowBar;
-use crate::{config::RsbrConfig, icons};
+use super::{Block, ShowBar};
+use crate::config::{RsbrConfig, RsbrNetworkConfig};
+use crate::hexcolor::ThemeColor;
+use crate::netlink::{self, Addresses, Wireless};
+use crate::sysfs;
 
-pub struct NetworkProperty;
+const FIELDS: &[&str] = &[
+    "icon",
+    "ssid",
+    "interface",
+    "type",
+    "link",
+    "ipv4",
+    "ipv6",
+    "signal",
+    "signal_dbm",
+    "frequency",
+    "band",
+    "bitrate",
+];
+const STATES: &[&str] = &["disconnected"];
+const ICONS: &[&str] = &["ethernet", "wifi", "vpn", "disconnected"];
+const ARPHRD_NONE: u32 = 65534;
+
+/// The interfaces the module reports on.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InterfaceSelection {
+    /// The interface of the default route
+    Auto,
+    /// Every interface of the list
+    List(Vec<String>),
+}
+
+impl<'de> Deserialize<'de> for InterfaceSelection {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum OneOrMany {
+            One(String),
+            Many(Vec<String>),
+        }
+
+        match OneOrMany::deserialize(deserializer)? {
+            OneOrMany::One(x) if x == "auto" => Ok(InterfaceSelection::Auto),
+            OneOrMany::One(x) => Ok(InterfaceSelection::List(vec![x])),
+            OneOrMany::Many(x) if x.is_empty() => {
+                Err(de::Error::custom("The list of interfaces is empty"))
+            }
+            OneOrMany::Many(x) => Ok(InterfaceSelection::List(x)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum InterfaceKind {
+    Ethernet,
+    Wifi,
+    /// tun and tap devices as well as WireGuard tunnels
+    Vpn,
+}
+
+impl InterfaceKind {
+    fn name(self) -> &'static str {
+        match self {
+            InterfaceKind::Ethernet => "ethernet",
+            InterfaceKind::Wifi => "wifi",
+            InterfaceKind::Vpn => "vpn",
+        }
+    }
+}
+
+struct NetworkInfo {
+    interface: String,
+    kind: InterfaceKind,
+    up: bool,
+    addresses: Addresses,
+    wireless: Wireless,
+}
+
+impl NetworkInfo {
+    /// A wireless interface is only connected once it joined a network.
+    fn connected(&self) -> bool {
+        self.up && (self.kind != InterfaceKind::Wifi || self.wireless.ssid.is_some())
+    }
+
+    /// Maps the signal strength from -100 dBm to -50 dBm onto 0 to 100%.
+    fn signal_percent(&self) -> Option<u32> {
+        self.wireless
+            .signal
+            .map(|x| (2 * (x + 100)).clamp(0, 100) as u32)
+    }
+
+    fn band(&self) -> &'static str {
+        match self.wireless.frequency {
+            Some(2400..=2500) => "2.4 GHz",
+            Some(4900..=5899) => "5 GHz",
+            Some(5925..=7125) => "6 GHz",
+            Some(57000..=71000) => "60 GHz",
+            _ => "",
+        }
+    }
+}
+
+pub struct NetworkProperty {
+    config: RsbrNetworkConfig,
+}
 
 impl NetworkProperty {
-    fn get_network(&self) -> Result<String, anyhow::Error> {
-        let output = Command::new("nmcli")
-            .arg("-t")
-            .arg("-f")
-            .arg("active,ssid")
-            .arg("dev")
-            .arg("wifi")
-            .output()?;
-
-        let output_str = String::from_utf8_lossy(&output.stdout);
-
-        for line in output_str.lines() {
-            if line.starts_with("yes:") {
-                return Ok(line[4..].to_string());
+    pub fn new(config: RsbrNetworkConfig) -> Self {
+        Self { config }
+    }
+
+    fn interface_kind(interface: &str) -> InterfaceKind {
+        let path = Path::new("/sys/class/net").join(interface);
+
+        if path.join("wireless").exists() || path.join("phy80211").exists() {
+            InterfaceKind::Wifi
+        } else if path.join("tun_flags").exists()
+            || sysfs::read(interface, "type").parse() == Ok(ARPHRD_NONE)
+            || sysfs::read(interface, "uevent")
+                .lines()
+                .any(|x| x == "DEVTYPE=wireguard")
+        {
+            InterfaceKind::Vpn
+        } else {
+            InterfaceKind::Ethernet
+        }
+    }
+
+    fn read_network(interface: &str) -> Result<NetworkInfo, anyhow::Error> {
+        if !Path::new("/sys/class/net").join(interface).exists() {
+            return Err(anyhow::anyhow!("Unknown network interface {interface}"));
+        }
+
+        let index: u32 = sysfs::read(interface, "ifindex").parse()?;
+        let kind = Self::interface_kind(interface);
+        let up = sysfs::is_up(interface);
+
+        let addresses = if up {
+            netlink::addresses(index)?
+        } else {
+            Addresses::default()
+        };
+        let wireless = if kind == InterfaceKind::Wifi && up {
+            netlink::wireless(index)?
+        } else {
+            Wireless::default()
+        };
+
+        Ok(NetworkInfo {
+            interface: interface.to_string(),
+            kind,
+            up,
+            addresses,
+            wireless,
+        })
+    }
+
+    /// Reads every selected interface. Listed interfaces that do not exist,
+    /// e.g. an unplugged USB adapter, are left out unless none exists.
+    fn read_networks(selection: InterfaceSelection) -> Result<Vec<NetworkInfo>, anyhow::Error> {
+        let interfaces = match selection {
+            InterfaceSelection::Auto => vec![sysfs::primary_interface()?],
+            InterfaceSelection::List(x) => x,
+        };
+
+        let mut networks = vec![];
+        let mut error = None;
+
+        for interface in &interfaces {
+            match Self::read_network(interface) {
+                Ok(x) => networks.push(x),
+                Err(x) => {
+                    error.get_or_insert(x);
+                }
             }
         }
 
-        Err(anyhow::anyhow!("Network Information"))
+        match error {
+            Some(x) if networks.is_empty() => Err(x),
+            _ => Ok(networks),
+        }
+    }
+
+    async fn get_networks(&self) -> Result<Vec<NetworkInfo>, anyhow::Error> {
+        let selection = self.config.interface.clone();
+        tokio::task::spawn_blocking(move || Self::read_networks(selection)).await?
+    }
+
+    fn render(&self, config: &RsbrConfig, network: NetworkInfo) -> String {
+        let icon = if network.connected() {
+            network.kind.name()
+        } else {
+            "disconnected"
+        };
+        // The wifi ramp goes by signal strength.
+        let signal = network.signal_percent();
+        let optional = |x: Option<u32>| x.map(|x| x.to_string()).unwrap_or_default();
+
+        self.config.format.render_fields(&[
+            (
+                "icon",
+                self.config
+                    .icons
+                    .get(
+                        config.icons,
+                        "network",
+                        icon,
+                        f64::from(signal.unwrap_or(100)),
+                    )
+                    .to_string(),
+            ),
+            ("band", network.band().to_string()),
+            ("signal", optional(signal)),
+            (
+                "signal_dbm",
+                network
+                    .wireless
+                    .signal
+                    .map(|x| x.to_string())
+                    .unwrap_or_default(),
+            ),
+            ("frequency", optional(network.wireless.frequency)),
+            (
+                "bitrate",
+                network
+                    .wireless
+                    .bitrate
+                    .map(|x| (f64::from(x) / 10.0).to_string())
+                    .unwrap_or_default(),
+            ),
+            ("ssid", network.wireless.ssid.unwrap_or_default()),
+            ("interface", network.interface),
+            ("type", network.kind.name().to_string()),
+            ("link", if network.up { "up" } else { "down" }.to_string()),
+            (
+                "ipv4",
+                network
+                    .addresses
+                    .ipv4
+                    .map(|x| x.to_string())
+                    .unwrap_or_default(),
+            ),
+            (
+                "ipv6",
+                network
+                    .addresses
+                    .ipv6
+                    .map(|x| x.to_string())
+                    .unwrap_or_default(),
+            ),
+        ])
     }
 }
 
 #[async_trait]
 impl ShowBar for NetworkProperty {
-    async fn show_bar(&self, config: &RsbrConfig, template: &str) -> String {
-        let network = match self.get_network() {
-            Ok(network_ssid) => {
-                if network_ssid != "" {
-                    format!("{} {}", icons::get_wifi_icon(&network_ssid), network_ssid)
-                } else {
-                    format!("{}", icons::get_wifi_icon(&network_ssid))
+    fn kind(&self) -> &'static str {
+        "network"
+    }
+
+    fn name(&self) -> &str {
+        self.config.name.as_deref().unwrap_or("network")
+    }
+
+    fn interval(&self) -> u64 {
+        self.config.interval
+    }
+
+    fn signal(&self) -> Option<i32> {
+        self.config.signal
+    }
+
+    fn timeout(&self) -> u64 {
+        self.config.timeout
+    }
+
+    fn colors(&self) -> Vec<&ThemeColor> {
+        [&self.config.fgcolor, &self.config.bgcolor]
+            .into_iter()
+            .chain(self.config.states.colors())
+            .collect()
+    }
+
+    fn colors_mut(&mut self) -> Vec<&mut ThemeColor> {
+        [&mut self.config.fgcolor, &mut self.config.bgcolor]
+            .into_iter()
+            .chain(self.config.states.colors_mut())
+            .collect()
+    }
+
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        self.config.states.validate(STATES)?;
+        self.config.icons.validate(ICONS)?;
+        self.config.format.validate(FIELDS)
+    }
+
+    async fn show_bar(&self, config: &RsbrConfig) -> Block {
+        let mut state = None;
+        let network = match self.get_networks().await {
+            Ok(x) => {
+                if !x.iter().any(|x| x.connected()) {
+                    state = Some("disconnected");
                 }
+
+                x.into_iter()
+                    .map(|x| self.render(config, x))
+                    .collect::<Vec<_>>()
+                    .join(&self.config.separator)
             }
             Err(x) => {
                 eprintln!("{x}");
-                "No Wifi Found".to_string()
+                state = Some("disconnected");
+                "No Network Found".to_string()
             }
         };
 
-        template.replace(
-            "{network}",
-            &format!(
-                "^c{}^^b{}^ {}",
-                &config.theme.get_color(&config.network.fgcolor),
-                &config.theme.get_color(&config.network.bgcolor),
-                network.as_str()
-            ),
-        )
+        let (fgcolor, bgcolor) = self.config.states.block_colors(
+            &config.theme,
+            state,
+            &self.config.fgcolor,
+            &self.config.bgcolor,
+        );
+
+        Block {
+            full_text: network,
+            fgcolor,
+            bgcolor,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wifi(signal: Option<i32>, frequency: Option<u32>) -> NetworkInfo {
+        NetworkInfo {
+            interface: "wlan0".to_string(),
+            kind: InterfaceKind::Wifi,
+            up: true,
+            addresses: Addresses::default(),
+            wireless: Wireless {
+                signal,
+                frequency,
+                ..Wireless::default()
+            },
+        }
+    }
+
+    #[test]
+    fn maps_the_signal_to_percent() {
+        for (signal, percent) in [
+            (None, None),
+            (Some(-120), Some(0)),
+            (Some(-100), Some(0)),
+            (Some(-99), Some(2)),
+            (Some(-75), Some(50)),
+            (Some(-51), Some(98)),
+            (Some(-50), Some(100)),
+            (Some(-30), Some(100)),
+        ] {
+            assert_eq!(wifi(signal, None).signal_percent(), percent, "{signal:?}");
+        }
+    }
+
+    #[test]
+    fn maps_the_frequency_to_the_band() {
+        for (frequency, band) in [
+            (None, ""),
+            (Some(2399), ""),
+            (Some(2400), "2.4 GHz"),
+            (Some(2412), "2.4 GHz"),
+            (Some(2500), "2.4 GHz"),
+            (Some(2501), ""),
+            (Some(4899), ""),
+            (Some(4900), "5 GHz"),
+            (Some(5180), "5 GHz"),
+            (Some(5899), "5 GHz"),
+            (Some(5900), ""),
+            (Some(5925), "6 GHz"),
+            (Some(7125), "6 GHz"),
+            (Some(7126), ""),
+            (Some(56999), ""),
+            (Some(57000), "60 GHz"),
+            (Some(71000), "60 GHz"),
+            (Some(71001), ""),
+        ] {
+            assert_eq!(wifi(None, frequency).band(), band, "{frequency:?}");
+        }
     }
 }
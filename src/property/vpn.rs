@@ -0,0 +1,303 @@
+use async_trait::async_trait;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use super::network::InterfaceSelection;
+use super::{Block, ShowBar};
+use crate::config::{RsbrConfig, RsbrVpnConfig};
+use crate::hexcolor::ThemeColor;
+use crate::netlink;
+use crate::sysfs;
+
+const FIELDS: &[&str] = &["icon", "name", "type", "handshake"];
+const DISCONNECTED_FIELDS: &[&str] = &["icon"];
+const STATES: &[&str] = &["disconnected", "stale"];
+const ICONS: &[&str] = &["connected", "disconnected"];
+const IFF_TUN: u32 = 0x1;
+const IFF_TAP: u32 = 0x2;
+
+struct Tunnel {
+    name: String,
+    kind: &'static str,
+    /// Seconds since the latest handshake of a WireGuard tunnel
+    handshake: Option<u64>,
+}
+
+pub struct VpnProperty {
+    config: RsbrVpnConfig,
+    /// Whether a failure to read a handshake has been reported
+    warned: Arc<AtomicBool>,
+}
+
+impl VpnProperty {
+    pub fn new(config: RsbrVpnConfig) -> Self {
+        Self {
+            config,
+            warned: Arc::default(),
+        }
+    }
+
+    /// Returns `wireguard`, `tun` or `tap` for tunnel interfaces, e.g. of
+    /// OpenVPN, and `None` for any other interface.
+    fn tunnel_kind(interface: &str) -> Option<&'static str> {
+        let uevent = sysfs::read(interface, "uevent");
+        if uevent.lines().any(|x| x == "DEVTYPE=wireguard") {
+            return Some("wireguard");
+        }
+
+        Self::tun_mode(&sysfs::read(interface, "tun_flags"))
+    }
+
+    /// Returns `tun` or `tap` for the `tun_flags` of a tun device, e.g.
+    /// `0x1001`.
+    fn tun_mode(flags: &str) -> Option<&'static str> {
+        let flags = u32::from_str_radix(flags.strip_prefix("0x")?, 16).ok()?;
+        if flags & IFF_TUN != 0 {
+            Some("tun")
+        } else if flags & IFF_TAP != 0 {
+            Some("tap")
+        } else {
+            None
+        }
+    }
+
+    /// Reading the handshake needs `CAP_NET_ADMIN`. Without it the age is
+    /// left out, which is reported once.
+    fn handshake_age(interface: &str, warned: &AtomicBool) -> Option<u64> {
+        let index = sysfs::read(interface, "ifindex").parse().ok()?;
+        let time = match netlink::wireguard_handshake(index) {
+            Ok(x) => x?,
+            Err(x) => {
+                if !warned.swap(true, Ordering::Relaxed) {
+                    let denied = x
+                        .downcast_ref::<io::Error>()
+                        .is_some_and(|x| x.kind() == io::ErrorKind::PermissionDenied);
+                    let hint = if denied {
+                        ", which needs CAP_NET_ADMIN"
+                    } else {
+                        ""
+                    };
+                    eprintln!("Could not read the WireGuard handshake of {interface}{hint}: {x}");
+                }
+                return None;
+            }
+        };
+
+        Some(
+            SystemTime::now()
+                .duration_since(time)
+                .unwrap_or_default()
+                .as_secs(),
+        )
+    }
+
+    /// Returns the active tunnels, limited to the configured interfaces.
+    fn read_tunnels(
+        selection: InterfaceSelection,
+        warned: &AtomicBool,
+    ) -> Result<Vec<Tunnel>, anyhow::Error> {
+        let listed = matches!(selection, InterfaceSelection::List(_));
+        let interfaces = match selection {
+            InterfaceSelection::Auto => sysfs::interfaces()?,
+            InterfaceSelection::List(x) => x,
+        };
+
+        Ok(interfaces
+            .into_iter()
+            .filter(|x| sysfs::is_up(x))
+            .filter_map(|name| {
+                let kind = Self::tunnel_kind(&name).filter(|x| Self::is_vpn(x, listed))?;
+                let handshake = match kind {
+                    "wireguard" => Self::handshake_age(&name, warned),
+                    _ => None,
+                };
+
+                Some(Tunnel {
+                    name,
+                    kind,
+                    handshake,
+                })
+            })
+            .collect())
+    }
+
+    /// tap devices also connect virtual machines, so they only count as a
+    /// VPN if they are listed in `interface`.
+    fn is_vpn(kind: &str, listed: bool) -> bool {
+        kind != "tap" || listed
+    }
+
+    async fn get_tunnels(&self) -> Result<Vec<Tunnel>, anyhow::Error> {
+        let selection = self.config.interface.clone();
+        let warned = self.warned.clone();
+
+        tokio::task::spawn_blocking(move || Self::read_tunnels(selection, &warned)).await?
+    }
+
+    /// Formats an age as e.g. `42s`, `5m 3s` or `2h 10m`.
+    fn format_age(seconds: u64) -> String {
+        match seconds {
+            0..=59 => format!("{seconds}s"),
+            60..=3599 => format!("{}m {}s", seconds / 60, seconds % 60),
+            _ => format!("{}h {}m", seconds / 3600, seconds % 3600 / 60),
+        }
+    }
+
+    fn render(&self, config: &RsbrConfig, tunnel: Tunnel) -> String {
+        self.config.format.render_fields(&[
+            (
+                "icon",
+                self.config
+                    .icons
+                    .get(config.icons, "vpn", "connected", 0.0)
+                    .to_string(),
+            ),
+            ("name", tunnel.name),
+            ("type", tunnel.kind.to_string()),
+            (
+                "handshake",
+                tunnel.handshake.map(Self::format_age).unwrap_or_default(),
+            ),
+        ])
+    }
+}
+
+#[async_trait]
+impl ShowBar for VpnProperty {
+    fn kind(&self) -> &'static str {
+        "vpn"
+    }
+
+    fn name(&self) -> &str {
+        self.config.name.as_deref().unwrap_or("vpn")
+    }
+
+    fn interval(&self) -> u64 {
+        self.config.interval
+    }
+
+    fn signal(&self) -> Option<i32> {
+        self.config.signal
+    }
+
+    fn timeout(&self) -> u64 {
+        self.config.timeout
+    }
+
+    fn colors(&self) -> Vec<&ThemeColor> {
+        [&self.config.fgcolor, &self.config.bgcolor]
+            .into_iter()
+            .chain(self.config.states.colors())
+            .collect()
+    }
+
+    fn colors_mut(&mut self) -> Vec<&mut ThemeColor> {
+        [&mut self.config.fgcolor, &mut self.config.bgcolor]
+            .into_iter()
+            .chain(self.config.states.colors_mut())
+            .collect()
+    }
+
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        self.config.states.validate(STATES)?;
+        self.config.icons.validate(ICONS)?;
+        self.config
+            .format_disconnected
+            .validate(DISCONNECTED_FIELDS)?;
+        self.config.format.validate(FIELDS)
+    }
+
+    async fn show_bar(&self, config: &RsbrConfig) -> Block {
+        let mut state = None;
+        let vpn = match self.get_tunnels().await {
+            Ok(x) if x.is_empty() => {
+                state = Some("disconnected");
+                self.config.format_disconnected.render_fields(&[(
+                    "icon",
+                    self.config
+                        .icons
+                        .get(config.icons, "vpn", "disconnected", 0.0)
+                        .to_string(),
+                )])
+            }
+            Ok(x) => {
+                // A WireGuard peer renews the handshake every two minutes
+                // while there is traffic.
+                let handshake = x.iter().filter_map(|x| x.handshake).min();
+                if handshake.is_some_and(|x| self.config.states.is_above("stale", x as f64, 180.0))
+                {
+                    state = Some("stale");
+                }
+
+                x.into_iter()
+                    .map(|x| self.render(config, x))
+                    .collect::<Vec<_>>()
+                    .join(&self.config.separator)
+            }
+            Err(x) => {
+                eprintln!("{x}");
+                state = Some("disconnected");
+                "No VPN Found".to_string()
+            }
+        };
+
+        let (fgcolor, bgcolor) = self.config.states.block_colors(
+            &config.theme,
+            state,
+            &self.config.fgcolor,
+            &self.config.bgcolor,
+        );
+
+        Block {
+            full_text: vpn,
+            fgcolor,
+            bgcolor,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_tun_mode() {
+        for (flags, mode) in [
+            ("0x1001", Some("tun")),
+            ("0x1002", Some("tap")),
+            ("0x5002", Some("tap")),
+            ("0x0", None),
+            ("1001", None),
+            ("0xzz", None),
+            ("", None),
+        ] {
+            assert_eq!(VpnProperty::tun_mode(flags), mode, "{flags}");
+        }
+    }
+
+    #[test]
+    fn only_counts_listed_tap_devices() {
+        assert!(VpnProperty::is_vpn("wireguard", false));
+        assert!(VpnProperty::is_vpn("tun", false));
+        assert!(!VpnProperty::is_vpn("tap", false));
+        assert!(VpnProperty::is_vpn("tap", true));
+    }
+
+    #[test]
+    fn formats_ages() {
+        for (seconds, age) in [
+            (0, "0s"),
+            (59, "59s"),
+            (60, "1m 0s"),
+            (183, "3m 3s"),
+            (3599, "59m 59s"),
+            (3600, "1h 0m"),
+            (7830, "2h 10m"),
+            (90061, "25h 1m"),
+        ] {
+            assert_eq!(VpnProperty::format_age(seconds), age, "{seconds}");
+        }
+    }
+}
@@ -0,0 +1,703 @@
+//! A minimal client of the PulseAudio native protocol, which is also spoken
+//! by pipewire-pulse. Only the introspection of sinks and sources and the
+//! subscription to their changes are implemented.
+
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration};
+
+const PROTOCOL_VERSION: u32 = 32;
+const COOKIE_LENGTH: usize = 256;
+const CONTROL_CHANNEL: u32 = u32::MAX;
+const INVALID_INDEX: u32 = u32::MAX;
+const VOLUME_NORM: u64 = 0x10000;
+
+const COMMAND_ERROR: u32 = 0;
+const COMMAND_REPLY: u32 = 2;
+const COMMAND_AUTH: u32 = 8;
+const COMMAND_SET_CLIENT_NAME: u32 = 9;
+const COMMAND_GET_SINK_INFO: u32 = 21;
+const COMMAND_GET_SOURCE_INFO: u32 = 23;
+const COMMAND_SUBSCRIBE: u32 = 35;
+const COMMAND_SUBSCRIBE_EVENT: u32 = 66;
+
+const DEVICE_STATE_RUNNING: u32 = 0;
+
+const SUBSCRIPTION_MASK_SINK: u32 = 0x0001;
+const SUBSCRIPTION_MASK_SOURCE: u32 = 0x0002;
+const SUBSCRIPTION_MASK_SERVER: u32 = 0x0080;
+
+const TAG_STRING: u8 = b't';
+const TAG_STRING_NULL: u8 = b'N';
+const TAG_U32: u8 = b'L';
+const TAG_U8: u8 = b'B';
+const TAG_U64: u8 = b'R';
+const TAG_S64: u8 = b'r';
+const TAG_SAMPLE_SPEC: u8 = b'a';
+const TAG_ARBITRARY: u8 = b'x';
+const TAG_BOOLEAN_TRUE: u8 = b'1';
+const TAG_BOOLEAN_FALSE: u8 = b'0';
+const TAG_TIMEVAL: u8 = b'T';
+const TAG_USEC: u8 = b'U';
+const TAG_CHANNEL_MAP: u8 = b'm';
+const TAG_CVOLUME: u8 = b'v';
+const TAG_PROPLIST: u8 = b'P';
+const TAG_VOLUME: u8 = b'V';
+
+/// Whether a device plays audio or records it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeviceKind {
+    Sink,
+    Source,
+}
+
+/// The state of a sink or source.
+pub struct DeviceInfo {
+    pub description: String,
+    /// The volume in percent, averaged over the channels
+    pub volume: u32,
+    pub muted: bool,
+    /// Whether a stream is playing to the sink or recording from the source
+    pub running: bool,
+}
+
+/// Serializes the arguments of a command.
+#[derive(Default)]
+struct TagWriter(Vec<u8>);
+
+impl TagWriter {
+    fn u32(&mut self, x: u32) -> &mut Self {
+        self.0.push(TAG_U32);
+        self.0.extend_from_slice(&x.to_be_bytes());
+        self
+    }
+
+    fn string(&mut self, x: Option<&str>) -> &mut Self {
+        match x {
+            Some(x) => {
+                self.0.push(TAG_STRING);
+                self.0.extend_from_slice(x.as_bytes());
+                self.0.push(0);
+            }
+            None => self.0.push(TAG_STRING_NULL),
+        }
+        self
+    }
+
+    fn arbitrary(&mut self, x: &[u8]) -> &mut Self {
+        self.0.push(TAG_ARBITRARY);
+        self.0.extend_from_slice(&(x.len() as u32).to_be_bytes());
+        self.0.extend_from_slice(x);
+        self
+    }
+
+    fn proplist(&mut self, properties: &[(&str, &str)]) -> &mut Self {
+        self.0.push(TAG_PROPLIST);
+        for (key, value) in properties {
+            let mut value = value.as_bytes().to_vec();
+            value.push(0);
+            self.string(Some(key))
+                .u32(value.len() as u32)
+                .arbitrary(&value);
+        }
+        self.string(None)
+    }
+}
+
+/// Deserializes the reply of a command.
+struct TagReader {
+    data: Vec<u8>,
+    position: usize,
+}
+
+impl TagReader {
+    fn take(&mut self, length: usize) -> Result<&[u8], anyhow::Error> {
+        let end = self.position + length;
+        if end > self.data.len() {
+            return Err(anyhow::anyhow!("Truncated PulseAudio packet"));
+        }
+
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn tag(&mut self) -> Result<u8, anyhow::Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn expect(&mut self, tag: u8) -> Result<(), anyhow::Error> {
+        match self.tag()? {
+            x if x == tag => Ok(()),
+            x => Err(anyhow::anyhow!(
+                "Unexpected tag {} in PulseAudio packet, expected {}",
+                x as char,
+                tag as char
+            )),
+        }
+    }
+
+    fn raw_u32(&mut self) -> Result<u32, anyhow::Error> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, anyhow::Error> {
+        self.expect(TAG_U32)?;
+        self.raw_u32()
+    }
+
+    fn string(&mut self) -> Result<Option<String>, anyhow::Error> {
+        match self.tag()? {
+            TAG_STRING_NULL => Ok(None),
+            TAG_STRING => {
+                let rest = &self.data[self.position..];
+                let Some(end) = rest.iter().position(|x| *x == 0) else {
+                    return Err(anyhow::anyhow!("Unterminated string in PulseAudio packet"));
+                };
+                let string = String::from_utf8_lossy(&rest[..end]).into_owned();
+                self.position += end + 1;
+                Ok(Some(string))
+            }
+            x => Err(anyhow::anyhow!(
+                "Unexpected tag {} in PulseAudio packet, expected a string",
+                x as char
+            )),
+        }
+    }
+
+    fn boolean(&mut self) -> Result<bool, anyhow::Error> {
+        match self.tag()? {
+            TAG_BOOLEAN_TRUE => Ok(true),
+            TAG_BOOLEAN_FALSE => Ok(false),
+            x => Err(anyhow::anyhow!(
+                "Unexpected tag {} in PulseAudio packet, expected a boolean",
+                x as char
+            )),
+        }
+    }
+
+    fn cvolume(&mut self) -> Result<Vec<u32>, anyhow::Error> {
+        self.expect(TAG_CVOLUME)?;
+        let channels = self.take(1)?[0];
+        (0..channels).map(|_| self.raw_u32()).collect()
+    }
+
+    /// Skips a value of any type.
+    fn skip(&mut self) -> Result<(), anyhow::Error> {
+        match self.tag()? {
+            TAG_STRING => {
+                self.position -= 1;
+                self.string()?;
+            }
+            TAG_STRING_NULL | TAG_BOOLEAN_TRUE | TAG_BOOLEAN_FALSE => {}
+            TAG_U8 => {
+                self.take(1)?;
+            }
+            TAG_U32 | TAG_VOLUME => {
+                self.take(4)?;
+            }
+            TAG_U64 | TAG_S64 | TAG_USEC | TAG_TIMEVAL => {
+                self.take(8)?;
+            }
+            TAG_SAMPLE_SPEC => {
+                self.take(6)?;
+            }
+            TAG_ARBITRARY => {
+                let length = self.raw_u32()? as usize;
+                self.take(length)?;
+            }
+            TAG_CHANNEL_MAP => {
+                let channels = self.take(1)?[0] as usize;
+                self.take(channels)?;
+            }
+            TAG_CVOLUME => {
+                let channels = self.take(1)?[0] as usize;
+                self.take(channels * 4)?;
+            }
+            TAG_PROPLIST => {
+                // Pairs of a key and its length and value, ended by a null key
+                while self.string()?.is_some() {
+                    self.skip()?;
+                    self.skip()?;
+                }
+            }
+            x => {
+                return Err(anyhow::anyhow!(
+                    "Unknown tag {} in PulseAudio packet",
+                    x as char
+                ))
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// An error reply of the server. Unlike a failed read or write, it leaves
+/// the connection usable.
+#[derive(Debug)]
+struct ServerError(u32);
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PulseAudio: {}", error_message(self.0))
+    }
+}
+
+impl Error for ServerError {}
+
+fn error_message(code: u32) -> &'static str {
+    match code {
+        1 => "Access denied",
+        2 => "Unknown command",
+        3 => "Invalid argument",
+        5 => "No such entity",
+        6 => "Connection refused",
+        7 => "Protocol error",
+        9 => "Authentication key invalid",
+        19 => "Not supported",
+        _ => "Unknown error",
+    }
+}
+
+fn socket_path() -> Result<PathBuf, anyhow::Error> {
+    if let Ok(server) = env::var("PULSE_SERVER") {
+        if let Some(x) = server.strip_prefix("unix:") {
+            return Ok(PathBuf::from(x));
+        }
+        if server.starts_with('/') {
+            return Ok(PathBuf::from(server));
+        }
+        return Err(anyhow::anyhow!("Unsupported PULSE_SERVER {server}"));
+    }
+
+    let runtime_dir = match env::var_os("XDG_RUNTIME_DIR") {
+        Some(x) => PathBuf::from(x),
+        // SAFETY: getuid cannot fail.
+        None => PathBuf::from(format!("/run/user/{}", unsafe { libc::getuid() })),
+    };
+
+    Ok(runtime_dir.join("pulse/native"))
+}
+
+/// Reads the authentication cookie. pipewire-pulse and servers that check
+/// the credentials of the socket accept any cookie.
+fn cookie() -> Vec<u8> {
+    let paths = env::var_os("PULSE_COOKIE")
+        .map(PathBuf::from)
+        .into_iter()
+        .chain(
+            dirs::home_dir()
+                .into_iter()
+                .flat_map(|x| [x.join(".config/pulse/cookie"), x.join(".pulse-cookie")]),
+        );
+
+    paths
+        .filter_map(|x| fs::read(x).ok())
+        .find(|x| x.len() == COOKIE_LENGTH)
+        .unwrap_or_else(|| vec![0; COOKIE_LENGTH])
+}
+
+/// Parses the reply to a sink or source info request. Both share the
+/// layout up to the device state.
+fn read_device_info(reply: &mut TagReader) -> Result<DeviceInfo, anyhow::Error> {
+    // index, name
+    reply.skip()?;
+    reply.skip()?;
+    let description = reply.string()?.unwrap_or_default();
+    // sample spec, channel map, owner module
+    for _ in 0..3 {
+        reply.skip()?;
+    }
+    let volumes = reply.cvolume()?;
+    let muted = reply.boolean()?;
+    // monitor index and name, latency, driver, flags, properties,
+    // requested latency, base volume
+    for _ in 0..8 {
+        reply.skip()?;
+    }
+    let state = reply.u32()?;
+
+    let volume = match volumes.len() {
+        0 => 0,
+        x => volumes.iter().map(|x| u64::from(*x)).sum::<u64>() / x as u64,
+    };
+
+    Ok(DeviceInfo {
+        description,
+        volume: ((volume * 100 + VOLUME_NORM / 2) / VOLUME_NORM) as u32,
+        muted,
+        running: state == DEVICE_STATE_RUNNING,
+    })
+}
+
+/// An authenticated connection to the server.
+pub struct Connection {
+    stream: UnixStream,
+    version: u32,
+    tag: u32,
+}
+
+impl Connection {
+    pub async fn connect() -> Result<Self, anyhow::Error> {
+        let path = socket_path()?;
+        let stream = UnixStream::connect(&path)
+            .await
+            .map_err(|x| anyhow::anyhow!("Could not connect to {}: {x}", path.display()))?;
+
+        let mut connection = Self {
+            stream,
+            version: PROTOCOL_VERSION,
+            tag: 0,
+        };
+
+        let mut reply = connection
+            .request(
+                COMMAND_AUTH,
+                TagWriter::default()
+                    .u32(PROTOCOL_VERSION)
+                    .arbitrary(&cookie()),
+            )
+            .await?;
+        // The upper bits are flags for shared memory transport.
+        connection.version = PROTOCOL_VERSION.min(reply.u32()? & 0xFFFF);
+
+        connection
+            .request(
+                COMMAND_SET_CLIENT_NAME,
+                TagWriter::default().proplist(&[("application.name", "rsbr")]),
+            )
+            .await?;
+
+        Ok(connection)
+    }
+
+    async fn send(&mut self, command: u32, args: &TagWriter) -> Result<u32, anyhow::Error> {
+        let tag = self.tag;
+        self.tag = self.tag.wrapping_add(1);
+
+        let mut payload = TagWriter::default();
+        payload.u32(command).u32(tag);
+        payload.0.extend_from_slice(&args.0);
+
+        let mut packet = Vec::with_capacity(20 + payload.0.len());
+        for x in [payload.0.len() as u32, CONTROL_CHANNEL, 0, 0, 0] {
+            packet.extend_from_slice(&x.to_be_bytes());
+        }
+        packet.extend_from_slice(&payload.0);
+        self.stream.write_all(&packet).await?;
+
+        Ok(tag)
+    }
+
+    /// Reads the next packet on the control channel, returning its command
+    /// and tag.
+    async fn receive(&mut self) -> Result<(u32, u32, TagReader), anyhow::Error> {
+        loop {
+            let mut header = [0; 20];
+            self.stream.read_exact(&mut header).await?;
+
+            let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+            let channel = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+
+            let mut data = vec![0; length as usize];
+            self.stream.read_exact(&mut data).await?;
+
+            if channel != CONTROL_CHANNEL {
+                continue;
+            }
+
+            let mut reader = TagReader { data, position: 0 };
+            let command = reader.u32()?;
+            let tag = reader.u32()?;
+
+            return Ok((command, tag, reader));
+        }
+    }
+
+    async fn request(
+        &mut self,
+        command: u32,
+        args: &TagWriter,
+    ) -> Result<TagReader, anyhow::Error> {
+        let tag = self.send(command, args).await?;
+
+        loop {
+            let (command, reply_tag, mut reader) = self.receive().await?;
+            if reply_tag != tag {
+                continue;
+            }
+
+            return match command {
+                COMMAND_REPLY => Ok(reader),
+                COMMAND_ERROR => Err(ServerError(reader.u32()?).into()),
+                x => Err(anyhow::anyhow!("Unexpected PulseAudio command {x}")),
+            };
+        }
+    }
+
+    /// Returns the state of the sink or source `name`, or of the default
+    /// one if no name is given.
+    pub async fn device_info(
+        &mut self,
+        kind: DeviceKind,
+        name: Option<&str>,
+    ) -> Result<DeviceInfo, anyhow::Error> {
+        let (command, default) = match kind {
+            DeviceKind::Sink => (COMMAND_GET_SINK_INFO, "@DEFAULT_SINK@"),
+            DeviceKind::Source => (COMMAND_GET_SOURCE_INFO, "@DEFAULT_SOURCE@"),
+        };
+
+        let mut reply = self
+            .request(
+                command,
+                TagWriter::default()
+                    .u32(INVALID_INDEX)
+                    .string(Some(name.unwrap_or(default))),
+            )
+            .await?;
+
+        read_device_info(&mut reply)
+    }
+
+    /// Returns a channel that yields whenever a sink, a source or the
+    /// default devices change, and the task that keeps the connection. The
+    /// connection is reestablished if the server restarts.
+    pub fn subscribe() -> (mpsc::UnboundedReceiver<()>, JoinHandle<()>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        let task = tokio::spawn(async move {
+            while !tx.is_closed() {
+                if let Err(x) = Self::forward_events(&tx).await {
+                    eprintln!("PulseAudio subscription failed: {x}");
+                }
+                sleep(Duration::from_secs(5)).await;
+            }
+        });
+
+        (rx, task)
+    }
+
+    async fn forward_events(tx: &mpsc::UnboundedSender<()>) -> Result<(), anyhow::Error> {
+        let mut connection = Self::connect().await?;
+        let mask = SUBSCRIPTION_MASK_SINK | SUBSCRIPTION_MASK_SOURCE | SUBSCRIPTION_MASK_SERVER;
+        connection
+            .request(COMMAND_SUBSCRIBE, TagWriter::default().u32(mask))
+            .await?;
+
+        // The server state may have changed while we were not connected.
+        if tx.send(()).is_err() {
+            return Ok(());
+        }
+
+        loop {
+            let (command, _, _) = connection.receive().await?;
+            if command == COMMAND_SUBSCRIBE_EVENT && tx.send(()).is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// A connection that is established on the first request and kept for the
+/// following ones.
+#[derive(Default)]
+pub struct Client(Mutex<Option<Connection>>);
+
+impl Client {
+    /// Like [`Connection::device_info`]. A kept connection that failed,
+    /// e.g. because the server restarted, is replaced by a new one.
+    pub async fn device_info(
+        &self,
+        kind: DeviceKind,
+        name: Option<&str>,
+    ) -> Result<DeviceInfo, anyhow::Error> {
+        let mut client = self.0.lock().await;
+
+        // The connection is taken out while in use, so that a request that
+        // is cancelled half way by the timeout does not leave it behind.
+        if let Some(mut connection) = client.take() {
+            match connection.device_info(kind, name).await {
+                Err(x) if !x.is::<ServerError>() => {}
+                result => {
+                    *client = Some(connection);
+                    return result;
+                }
+            }
+        }
+
+        let mut connection = Connection::connect().await?;
+        let result = connection.device_info(kind, name).await;
+        *client = Some(connection);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reader(data: &[u8]) -> TagReader {
+        TagReader {
+            data: data.to_vec(),
+            position: 0,
+        }
+    }
+
+    /// The arguments of a GET_SINK_INFO reply of a stereo sink at protocol
+    /// version 32, after the command and tag.
+    fn sink_info(volume: &[u8], muted: u8, state: u8) -> Vec<u8> {
+        [
+            &b"L\0\0\0\x01"[..],
+            b"talsa_output.pci-0000_00_1f.3.analog-stereo\0",
+            b"tBuilt-in Audio Analog Stereo\0",
+            // s16le, 2 channels, 48000 Hz
+            b"a\x03\x02\0\0\xbb\x80",
+            b"m\x02\x01\x02",
+            b"L\0\0\0\x07",
+            b"v\x02",
+            volume,
+            &[muted],
+            b"L\0\0\0\x02",
+            b"talsa_output.pci-0000_00_1f.3.analog-stereo.monitor\0",
+            b"U\0\0\0\0\0\0\x9c\x40",
+            b"tmodule-alsa-card.c\0",
+            b"L\0\0\0\x37",
+            b"Ptdevice.api\0L\0\0\0\x05x\0\0\0\x05alsa\0N",
+            b"U\0\0\0\0\0\0\0\0",
+            b"V\0\x01\0\0",
+            &[b'L', 0, 0, 0, state],
+            // volume steps, card, ports and formats follow
+            b"L\0\x01\0\x01",
+            b"L\0\0\0\0",
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn writes_tags() {
+        let mut writer = TagWriter::default();
+        writer
+            .u32(5)
+            .string(Some("ab"))
+            .string(None)
+            .arbitrary(&[1, 2]);
+
+        assert_eq!(writer.0, b"L\0\0\0\x05tab\0Nx\0\0\0\x02\x01\x02");
+    }
+
+    #[test]
+    fn writes_proplist() {
+        let mut writer = TagWriter::default();
+        writer.proplist(&[("application.name", "rsbr")]);
+
+        assert_eq!(
+            writer.0,
+            b"Ptapplication.name\0L\0\0\0\x05x\0\0\0\x05rsbr\0N"
+        );
+    }
+
+    #[test]
+    fn reads_written_tags() {
+        let mut writer = TagWriter::default();
+        writer.u32(u32::MAX).string(Some("sink")).string(None);
+        let mut reader = reader(&writer.0);
+
+        assert_eq!(reader.u32().unwrap(), u32::MAX);
+        assert_eq!(reader.string().unwrap().as_deref(), Some("sink"));
+        assert_eq!(reader.string().unwrap(), None);
+        assert!(reader.tag().is_err());
+    }
+
+    #[test]
+    fn reads_boolean_and_cvolume() {
+        let mut reader = reader(b"10v\x02\0\x01\0\0\0\0\x80\0");
+
+        assert!(reader.boolean().unwrap());
+        assert!(!reader.boolean().unwrap());
+        assert_eq!(reader.cvolume().unwrap(), vec![0x10000, 0x8000]);
+    }
+
+    #[test]
+    fn skips_every_tag() {
+        let mut reader = reader(
+            &[
+                &b"tname\0NL\0\0\0\x01B\x02R\0\0\0\0\0\0\0\x01"[..],
+                b"a\x03\x02\0\0\xbb\x80m\x02\x01\x02v\x01\0\x01\0\0",
+                b"x\0\0\0\x02\x01\x02Ptkey\0L\0\0\0\x02x\0\0\0\x02v\0N",
+                b"L\0\0\0\x2a",
+            ]
+            .concat(),
+        );
+
+        for _ in 0..10 {
+            reader.skip().unwrap();
+        }
+        assert_eq!(reader.u32().unwrap(), 42);
+    }
+
+    #[test]
+    fn rejects_malformed_packets() {
+        assert!(reader(b"t").u32().is_err());
+        assert!(reader(b"L\0\0").u32().is_err());
+        assert!(reader(b"tno end").string().is_err());
+        assert!(reader(b"x").skip().is_err());
+        assert!(reader(b"v\x02\0\0").cvolume().is_err());
+    }
+
+    #[test]
+    fn parses_sink_info() {
+        let data = sink_info(b"\0\0\x80\0\0\0\x80\0", b'0', 0);
+        let sink = read_device_info(&mut reader(&data)).unwrap();
+
+        assert_eq!(sink.description, "Built-in Audio Analog Stereo");
+        assert_eq!(sink.volume, 50);
+        assert!(!sink.muted);
+        assert!(sink.running);
+    }
+
+    #[test]
+    fn averages_channel_volumes() {
+        // 100% and 35%, the idle state follows
+        let data = sink_info(b"\0\x01\0\0\0\0\x59\x9a", b'1', 1);
+        let sink = read_device_info(&mut reader(&data)).unwrap();
+
+        assert_eq!(sink.volume, 68);
+        assert!(sink.muted);
+        assert!(!sink.running);
+    }
+
+    #[test]
+    fn rejects_truncated_sink_info() {
+        let data = sink_info(b"\0\0\x80\0\0\0\x80\0", b'0', 0);
+
+        assert!(read_device_info(&mut reader(&data[..data.len() / 2])).is_err());
+    }
+
+    /// Talks to the server at `$XDG_RUNTIME_DIR/pulse/native`, or at
+    /// `$PULSE_SERVER`.
+    #[tokio::test]
+    #[ignore = "needs a running PulseAudio or pipewire-pulse server"]
+    async fn queries_the_server() {
+        let client = Client::default();
+
+        for _ in 0..2 {
+            let sink = client.device_info(DeviceKind::Sink, None).await.unwrap();
+            assert!(!sink.description.is_empty());
+        }
+        client.device_info(DeviceKind::Source, None).await.unwrap();
+        assert!(client
+            .device_info(DeviceKind::Sink, Some("rsbr-no-such-sink"))
+            .await
+            .is_err());
+        assert!(client.0.lock().await.is_some());
+    }
+}
@@ -0,0 +1,92 @@
+use futures_util::stream::StreamExt;
+use inotify::{Inotify, WatchDescriptor, WatchMask, Watches};
+use std::ffi::OsString;
+use std::path::Path;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::mpsc;
+
+/// Returns a channel that yields whenever the config file at `config_path`,
+/// or the file it links to, changes or rsbr receives `SIGHUP`.
+pub fn watch(config_path: &Path) -> mpsc::UnboundedReceiver<()> {
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    match signal(SignalKind::hangup()) {
+        Ok(mut stream) => {
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                while stream.recv().await.is_some() {
+                    if tx.send(()).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        Err(x) => eprintln!("Could not listen for SIGHUP: {x}"),
+    }
+
+    let stream = Inotify::init().and_then(|inotify| inotify.into_event_stream([0; 1024]));
+
+    match stream {
+        Ok(mut stream) => {
+            let config_path = config_path.to_path_buf();
+            let mut files = add_watches(stream.watches(), &config_path);
+
+            tokio::spawn(async move {
+                while let Some(event) = stream.next().await {
+                    match event {
+                        Ok(x)
+                            if files
+                                .iter()
+                                .any(|(wd, name)| *wd == x.wd && x.name.as_ref() == Some(name)) =>
+                        {
+                            // A replaced symlink may point to another file.
+                            files = add_watches(stream.watches(), &config_path);
+                            if tx.send(()).is_err() {
+                                break;
+                            }
+                        }
+                        Ok(_) => {}
+                        Err(x) => {
+                            eprintln!("Error while watching config file: {x}");
+                            break;
+                        }
+                    }
+                }
+            });
+        }
+        Err(x) => eprintln!("Could not watch {}: {x}", config_path.display()),
+    }
+
+    rx
+}
+
+/// Watches the directory of the config file and, if the config file is a
+/// symlink, the directory of its target. Editors usually replace the file
+/// instead of writing to it, so the events are matched by file name.
+/// Returns the watches with the file name to look for in each.
+fn add_watches(mut watches: Watches, config_path: &Path) -> Vec<(WatchDescriptor, OsString)> {
+    let mut paths = vec![config_path.to_path_buf()];
+    paths.extend(config_path.canonicalize());
+    paths.dedup();
+
+    paths
+        .iter()
+        .filter_map(|path| {
+            let directory = match path.parent() {
+                Some(x) if x.as_os_str().is_empty() => Path::new("."),
+                Some(x) => x,
+                None => Path::new("/"),
+            };
+            let file_name = path.file_name()?.to_os_string();
+            let mask = WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::CREATE;
+
+            match watches.add(directory, mask) {
+                Ok(x) => Some((x, file_name)),
+                Err(x) => {
+                    eprintln!("Could not watch {}: {x}", directory.display());
+                    None
+                }
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,153 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::format::SnippetError;
+use crate::hexcolor::{HexColor, Theme, ThemeColor};
+
+/// The colors a module switches to while it is in a state, e.g. a low
+/// battery, and the threshold at which it enters it.
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct StateConfig {
+    pub threshold: Option<f64>,
+    pub fgcolor: Option<ThemeColor>,
+    pub bgcolor: Option<ThemeColor>,
+}
+
+/// The `states` table of a module, keyed by state name.
+#[derive(Deserialize, Clone, Default)]
+pub struct States(HashMap<String, StateConfig>);
+
+impl States {
+    /// Builds the default states of a module from names and foreground
+    /// colors.
+    pub fn with_fgcolors(states: &[(&str, &str)]) -> Self {
+        States(
+            states
+                .iter()
+                .map(|(name, color)| {
+                    let state = StateConfig {
+                        fgcolor: Some(ThemeColor::from_str(color).unwrap()),
+                        ..StateConfig::default()
+                    };
+                    (name.to_string(), state)
+                })
+                .collect(),
+        )
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+
+    /// Whether `value` is at or below the threshold of the state. A state
+    /// without a configured threshold uses `default`.
+    pub fn is_below(&self, name: &str, value: f64, default: f64) -> bool {
+        self.contains(name) && value <= self.threshold(name, default)
+    }
+
+    /// Whether `value` is at or above the threshold of the state. A state
+    /// without a configured threshold uses `default`.
+    pub fn is_above(&self, name: &str, value: f64, default: f64) -> bool {
+        self.contains(name) && value >= self.threshold(name, default)
+    }
+
+    fn threshold(&self, name: &str, default: f64) -> f64 {
+        self.0
+            .get(name)
+            .and_then(|x| x.threshold)
+            .unwrap_or(default)
+    }
+
+    /// Resolves the colors of the block, `state` overriding the colors of
+    /// the module if it sets them.
+    pub fn block_colors(
+        &self,
+        theme: &Theme,
+        state: Option<&str>,
+        fgcolor: &ThemeColor,
+        bgcolor: &ThemeColor,
+    ) -> (HexColor, HexColor) {
+        let state = state.and_then(|x| self.0.get(x));
+        let fgcolor = state.and_then(|x| x.fgcolor.as_ref()).unwrap_or(fgcolor);
+        let bgcolor = state.and_then(|x| x.bgcolor.as_ref()).unwrap_or(bgcolor);
+
+        (theme.get_color(fgcolor), theme.get_color(bgcolor))
+    }
+
+    pub fn colors(&self) -> impl Iterator<Item = &ThemeColor> {
+        self.0
+            .values()
+            .flat_map(|x| [x.fgcolor.as_ref(), x.bgcolor.as_ref()])
+            .flatten()
+    }
+
+    pub fn colors_mut(&mut self) -> impl Iterator<Item = &mut ThemeColor> {
+        self.0
+            .values_mut()
+            .flat_map(|x| [x.fgcolor.as_mut(), x.bgcolor.as_mut()])
+            .flatten()
+    }
+
+    /// Fails on the first state that is not one of `names`.
+    pub fn validate(&self, names: &[&str]) -> Result<(), anyhow::Error> {
+        match self.0.keys().find(|x| !names.contains(&x.as_str())) {
+            Some(x) => Err(SnippetError {
+                snippet: format!("states.{x}"),
+                message: format!("Unknown state {x}, expected one of {}", names.join(", ")),
+            }
+            .into()),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn states(toml: &str) -> States {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn uses_the_default_threshold() {
+        let states = states("[low]\n[high]\n");
+
+        assert!(states.is_below("low", 20.0, 20.0));
+        assert!(!states.is_below("low", 20.5, 20.0));
+        assert!(states.is_above("high", 80.0, 80.0));
+        assert!(!states.is_above("high", 79.5, 80.0));
+    }
+
+    #[test]
+    fn prefers_the_configured_threshold() {
+        let states = states("[low]\nthreshold = 10.0\n");
+
+        assert!(!states.is_below("low", 15.0, 20.0));
+        assert!(states.is_below("low", 10.0, 20.0));
+    }
+
+    #[test]
+    fn ignores_states_that_are_not_configured() {
+        let states = states("[low]\n");
+
+        assert!(!states.is_below("critical", 0.0, 100.0));
+        assert!(!states.is_above("critical", 100.0, 0.0));
+    }
+
+    #[test]
+    fn overrides_block_colors() {
+        let states = states("[low]\nbgcolor = \"red\"\n");
+        let fgcolor = ThemeColor::from_str("white").unwrap();
+        let bgcolor = ThemeColor::from_str("black").unwrap();
+        let theme = Theme::default();
+
+        let (fg, bg) = states.block_colors(&theme, Some("low"), &fgcolor, &bgcolor);
+        assert_eq!((fg.0.as_str(), bg.0.as_str()), ("#CCCCCC", "#FF0000"));
+
+        let (fg, bg) = states.block_colors(&theme, None, &fgcolor, &bgcolor);
+        assert_eq!((fg.0.as_str(), bg.0.as_str()), ("#CCCCCC", "#000000"));
+    }
+}
@@ -0,0 +1,59 @@
+//! Network interface attributes read from `/sys/class/net`, shared by the
+//! network, bandwidth and vpn modules.
+
+use std::fs;
+
+use crate::netlink;
+
+const ARPHRD_LOOPBACK: u32 = 772;
+
+/// Reads an attribute of the interface, e.g. `operstate`. Missing
+/// attributes read as an empty string.
+pub fn read(interface: &str, file: &str) -> String {
+    fs::read_to_string(format!("/sys/class/net/{interface}/{file}"))
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+/// Whether the link of the interface is up. Tunnels report an unknown
+/// operational state, so their carrier decides.
+pub fn is_up(interface: &str) -> bool {
+    match read(interface, "operstate").as_str() {
+        "up" => true,
+        "unknown" => read(interface, "carrier") == "1",
+        _ => false,
+    }
+}
+
+/// The interface names without the loopback device, sorted by name.
+pub fn interfaces() -> Result<Vec<String>, anyhow::Error> {
+    let mut interfaces: Vec<String> = fs::read_dir("/sys/class/net")?
+        .flatten()
+        .map(|x| x.file_name().to_string_lossy().into_owned())
+        .filter(|x| read(x, "type").parse() != Ok(ARPHRD_LOOPBACK))
+        .collect();
+    interfaces.sort();
+
+    Ok(interfaces)
+}
+
+/// Returns the interface of the default route. Without one, the first
+/// interface whose link is up is picked.
+pub fn primary_interface() -> Result<String, anyhow::Error> {
+    let interfaces = interfaces()?;
+
+    if let Some(index) = netlink::default_route()? {
+        let index = index.to_string();
+        if let Some(x) = interfaces.iter().find(|x| read(x, "ifindex") == index) {
+            return Ok(x.clone());
+        }
+    }
+
+    interfaces
+        .iter()
+        .find(|x| is_up(x))
+        .or(interfaces.first())
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("No network interface found"))
+}
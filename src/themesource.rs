@@ -0,0 +1,304 @@
+use serde::de;
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::str::FromStr;
+
+use crate::hexcolor::HexColor;
+
+/// The names the 16 terminal colors of a source are imported as.
+const ANSI_NAMES: [&str; 16] = [
+    "black",
+    "red",
+    "green",
+    "yellow",
+    "blue",
+    "magenta",
+    "cyan",
+    "white",
+    "bright_black",
+    "bright_red",
+    "bright_green",
+    "bright_yellow",
+    "bright_blue",
+    "bright_magenta",
+    "bright_cyan",
+    "bright_white",
+];
+
+/// The base16 slots of the 16 terminal colors, as used by base16-shell.
+const BASE16_ANSI: [&str; 16] = [
+    "base00", "base08", "base0b", "base0a", "base0d", "base0e", "base0c", "base05", "base03",
+    "base08", "base0b", "base0a", "base0d", "base0e", "base0c", "base07",
+];
+
+/// An external color scheme the theme is imported from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThemeSource {
+    /// The `*.color0` to `*.color15`, `*.background` and `*.foreground`
+    /// resources of `xrdb -query`
+    Xresources,
+    /// pywal's `~/.cache/wal/colors.json`
+    Pywal,
+    /// A base16 scheme file
+    Base16,
+}
+
+impl ThemeSource {
+    /// Reads the colors of the source, either from `path` or from the
+    /// default location of the source.
+    pub fn load(self, path: Option<&Path>) -> Result<HashMap<String, HexColor>, anyhow::Error> {
+        let mut colors = match self {
+            ThemeSource::Xresources => {
+                let content = match path {
+                    Some(x) => fs::read_to_string(x)?,
+                    None => {
+                        let output = Command::new("xrdb").arg("-query").output()?;
+                        if !output.status.success() {
+                            return Err(anyhow::anyhow!(
+                                "xrdb -query failed: {}",
+                                String::from_utf8_lossy(&output.stderr).trim()
+                            ));
+                        }
+                        String::from_utf8_lossy(&output.stdout).into_owned()
+                    }
+                };
+                parse_xresources(&content)
+            }
+            ThemeSource::Pywal => {
+                let path = match path {
+                    Some(x) => x.to_path_buf(),
+                    None => dirs::cache_dir()
+                        .unwrap_or_default()
+                        .join("wal/colors.json"),
+                };
+                parse_pywal(&fs::read_to_string(path)?)?
+            }
+            ThemeSource::Base16 => {
+                let Some(path) = path else {
+                    return Err(anyhow::anyhow!(
+                        "base16 needs the scheme file in source_path"
+                    ));
+                };
+                parse_base16(&fs::read_to_string(path)?)
+            }
+        };
+
+        // Terminal palettes have no purple, it is magenta there.
+        for (purple, magenta) in [("purple", "magenta"), ("bright_purple", "bright_magenta")] {
+            if let Some(x) = colors.get(magenta).cloned() {
+                colors.entry(purple.to_string()).or_insert(x);
+            }
+        }
+
+        Ok(colors)
+    }
+}
+
+/// Reads `*.colorN`, `*.background` and `*.foreground` resources, e.g.
+/// `*.color0: #282a36` or `*color0: #282a36`.
+fn parse_xresources(content: &str) -> HashMap<String, HexColor> {
+    let mut colors = HashMap::new();
+
+    for line in content.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let Some(key) = key.trim().strip_prefix('*') else {
+            continue;
+        };
+        let key = key.trim_start_matches('.');
+        let Ok(color) = HexColor::from_str(value.trim()) else {
+            continue;
+        };
+
+        let name = match key {
+            "background" | "foreground" => key,
+            _ => match key
+                .strip_prefix("color")
+                .and_then(|x| x.parse::<usize>().ok())
+            {
+                Some(x) if x < ANSI_NAMES.len() => ANSI_NAMES[x],
+                _ => continue,
+            },
+        };
+        colors.insert(name.to_string(), color);
+    }
+
+    colors
+}
+
+#[derive(Deserialize)]
+struct PywalColors {
+    special: HashMap<String, String>,
+    colors: HashMap<String, String>,
+}
+
+fn parse_pywal(content: &str) -> Result<HashMap<String, HexColor>, anyhow::Error> {
+    let pywal: PywalColors = serde_json::from_str(content)?;
+    let mut colors = HashMap::new();
+
+    for name in ["background", "foreground"] {
+        if let Some(x) = pywal.special.get(name) {
+            colors.insert(name.to_string(), HexColor::from_str(x)?);
+        }
+    }
+
+    for (index, name) in ANSI_NAMES.iter().enumerate() {
+        if let Some(x) = pywal.colors.get(&format!("color{index}")) {
+            colors.insert(name.to_string(), HexColor::from_str(x)?);
+        }
+    }
+
+    Ok(colors)
+}
+
+/// Reads the `base00` to `base0F` keys of a base16 scheme, with or without
+/// a `#` and a `palette` section. The slots are imported under their own
+/// names as well as the terminal color names.
+fn parse_base16(content: &str) -> HashMap<String, HexColor> {
+    let mut slots = HashMap::new();
+
+    for line in content.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let key = key.trim().trim_matches(['"', '\'']).to_lowercase();
+        let is_slot = key.len() == 6
+            && key.starts_with("base0")
+            && key[5..].chars().all(|x| x.is_ascii_hexdigit());
+        if !is_slot {
+            continue;
+        }
+
+        let value = value.trim();
+        let value = match value.strip_prefix(['"', '\'']) {
+            Some(x) => x.split(['"', '\'']).next().unwrap_or_default(),
+            None => value.split_whitespace().next().unwrap_or_default(),
+        };
+
+        if let Ok(color) = HexColor::from_str(&format!("#{}", value.trim_start_matches('#'))) {
+            slots.insert(key, color);
+        }
+    }
+
+    let mut colors: HashMap<String, HexColor> = BASE16_ANSI
+        .iter()
+        .zip(ANSI_NAMES)
+        .filter_map(|(slot, name)| Some((name.to_string(), slots.get(*slot)?.clone())))
+        .collect();
+
+    for (name, slot) in [("background", "base00"), ("foreground", "base05")] {
+        if let Some(x) = slots.get(slot) {
+            colors.insert(name.to_string(), x.clone());
+        }
+    }
+
+    colors.extend(slots);
+
+    colors
+}
+
+impl FromStr for ThemeSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "xresources" | "xrdb" => Ok(ThemeSource::Xresources),
+            "pywal" | "wal" => Ok(ThemeSource::Pywal),
+            "base16" => Ok(ThemeSource::Base16),
+            _ => Err(anyhow::anyhow!("{} is not a valid theme source", s)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ThemeSource {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s: String = Deserialize::deserialize(deserializer)?;
+
+        match ThemeSource::from_str(&s) {
+            Ok(x) => Ok(x),
+            Err(x) => Err(de::Error::custom(x)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn color(colors: &HashMap<String, HexColor>, name: &str) -> String {
+        colors[name].0.clone()
+    }
+
+    #[test]
+    fn parses_xresources() {
+        let colors = parse_xresources(
+            "*.background:\t#282a36\n\
+             *foreground: #f8f8f2\n\
+             *.color1: #ff5555\n\
+             *color12:#bd93f9\n\
+             URxvt.color2: #50fa7b\n\
+             *.color16: #000000\n\
+             *.color3: rgb:ff/ff/00\n\
+             ! *.color4: #0000ff\n",
+        );
+
+        assert_eq!(color(&colors, "background"), "#282a36");
+        assert_eq!(color(&colors, "foreground"), "#f8f8f2");
+        assert_eq!(color(&colors, "red"), "#ff5555");
+        assert_eq!(color(&colors, "bright_blue"), "#bd93f9");
+        assert_eq!(colors.len(), 4);
+    }
+
+    #[test]
+    fn parses_pywal() {
+        let colors = parse_pywal(
+            r##"{
+                "wallpaper": "/tmp/wall.png",
+                "special": {"background": "#1d1f21", "foreground": "#c5c8c6", "cursor": "#c5c8c6"},
+                "colors": {"color0": "#1d1f21", "color5": "#b294bb", "color15": "#ffffff"}
+            }"##,
+        )
+        .unwrap();
+
+        assert_eq!(color(&colors, "background"), "#1d1f21");
+        assert_eq!(color(&colors, "black"), "#1d1f21");
+        assert_eq!(color(&colors, "magenta"), "#b294bb");
+        assert_eq!(color(&colors, "bright_white"), "#ffffff");
+        assert!(!colors.contains_key("cursor"));
+
+        assert!(parse_pywal(r#"{"special": {}, "colors": {"color1": "red"}}"#).is_err());
+        assert!(parse_pywal("colors").is_err());
+    }
+
+    #[test]
+    fn parses_base16() {
+        let colors = parse_base16(
+            "scheme: \"Tomorrow Night\"\n\
+             author: \"Chris Kempson\"\n\
+             base00: \"1d1f21\"\n\
+             base05: 'c5c8c6' # foreground\n\
+             palette:\n  \
+               base08: \"#CC6666\"\n  \
+               base0D: 81a2be\n\
+             base0G: \"123456\"\n",
+        );
+
+        assert_eq!(color(&colors, "background"), "#1d1f21");
+        assert_eq!(color(&colors, "black"), "#1d1f21");
+        assert_eq!(color(&colors, "foreground"), "#c5c8c6");
+        assert_eq!(color(&colors, "white"), "#c5c8c6");
+        assert_eq!(color(&colors, "red"), "#CC6666");
+        assert_eq!(color(&colors, "bright_red"), "#CC6666");
+        assert_eq!(color(&colors, "blue"), "#81a2be");
+        assert_eq!(color(&colors, "base0d"), "#81a2be");
+        assert!(!colors.contains_key("base0g"));
+        assert!(!colors.contains_key("green"));
+    }
+}